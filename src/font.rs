@@ -4,9 +4,16 @@ use {
         Weight,
     },
     log::error,
-    std::default::Default,
+    std::{default::Default, path::PathBuf},
 };
 
+/// Resolve the on-disk font file backing `family`, for subsystems (e.g. the
+/// rustybuzz shaper) that need the raw font bytes rather than a `crossfont::FontKey`.
+pub fn resolve_font_path(family: &str) -> Option<PathBuf> {
+    let fc = fontconfig::Fontconfig::new()?;
+    fc.find(family, None).map(|m| m.path)
+}
+
 /// Description of the normal font.
 #[derive(Debug, Default, Clone)]
 pub struct FontDescription {
@@ -14,6 +21,27 @@ pub struct FontDescription {
     pub style: Option<String>,
 }
 
+/// The four style variants rasterized for a given font family/size.
+#[derive(Debug, Clone, Copy)]
+pub struct FontKeys {
+    pub regular: FontKey,
+    pub bold: FontKey,
+    pub italic: FontKey,
+    pub bold_italic: FontKey,
+}
+
+impl FontKeys {
+    /// Pick the face matching `(bold, italic)`.
+    pub fn select(&self, bold: bool, italic: bool) -> FontKey {
+        match (bold, italic) {
+            (false, false) => self.regular,
+            (true, false) => self.bold,
+            (false, true) => self.italic,
+            (true, true) => self.bold_italic,
+        }
+    }
+}
+
 /// Description of the font.
 pub struct Font {
     pub normal: FontDescription,
@@ -60,13 +88,34 @@ impl Font {
         FontDesc::new(desc.family.clone(), style)
     }
 
-    pub fn compute_font_keys(&mut self) -> Result<FontKey, crossfont::Error> {
+    pub fn compute_font_keys(&mut self) -> Result<FontKeys, crossfont::Error> {
         let size = self.size;
 
         let regular_desc = Self::make_desc(&self.normal, Slant::Normal, Weight::Normal);
         let regular = self.load_regular_font(&regular_desc, size)?;
 
-        Ok(regular)
+        // `make_desc` ignores slant/weight when a specific style name is set, so
+        // the variants are described by family only.
+        let variant = FontDescription {
+            family: self.normal.family.clone(),
+            style: None,
+        };
+
+        let bold_desc = Self::make_desc(&variant, Slant::Normal, Weight::Bold);
+        let bold = self.load_regular_font(&bold_desc, size)?;
+
+        let italic_desc = Self::make_desc(&variant, Slant::Italic, Weight::Normal);
+        let italic = self.load_regular_font(&italic_desc, size)?;
+
+        let bold_italic_desc = Self::make_desc(&variant, Slant::Italic, Weight::Bold);
+        let bold_italic = self.load_regular_font(&bold_italic_desc, size)?;
+
+        Ok(FontKeys {
+            regular,
+            bold,
+            italic,
+            bold_italic,
+        })
     }
 
     pub fn load_regular_font(
@@ -82,11 +131,10 @@ impl Font {
         })
     }
 
-    /// Calculate font metrics without access to a glyph cache.
-    pub fn metrics(&mut self) -> Result<crossfont::Metrics, crossfont::Error> {
-        let regular_desc = Self::make_desc(&self.normal, Slant::Normal, Weight::Normal);
-        let regular = self.load_regular_font(&regular_desc, self.size)?;
-        self.rasterizer.metrics(regular, self.size)
+    /// Calculate font metrics for an already-loaded `font_key`, instead of loading
+    /// (and rasterizing) the font a second time.
+    pub fn metrics(&mut self, font_key: FontKey) -> Result<crossfont::Metrics, crossfont::Error> {
+        self.rasterizer.metrics(font_key, self.size)
     }
 
     pub fn get_glyph(&mut self, glyph_key: GlyphKey) -> Result<RasterizedGlyph, crossfont::Error> {
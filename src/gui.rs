@@ -1,5 +1,10 @@
 use {
-    crate::shader,
+    crate::{
+        font,
+        parser::{TermGrid, Underline},
+        shape::{Shaper, ShapedCell},
+        text::{self, CellFlags, Decoration, Renderer},
+    },
     glutin::{
         self,
         dpi::{PhysicalPosition, PhysicalSize},
@@ -7,13 +12,13 @@ use {
         window::{CursorIcon, Fullscreen, WindowBuilder},
         ContextBuilder, PossiblyCurrent, WindowedContext,
     },
-    log::info,
+    log::{error, info},
 };
 
 #[derive(Debug)]
 pub enum Error {
     Glutin(glutin::CreationError),
-    Shader(shader::CreationError),
+    Shader(text::CreationError),
     Other(String),
 }
 
@@ -23,8 +28,8 @@ impl From<glutin::CreationError> for Error {
     }
 }
 
-impl From<shader::CreationError> for Error {
-    fn from(err: shader::CreationError) -> Self {
+impl From<text::CreationError> for Error {
+    fn from(err: text::CreationError) -> Self {
         Self::Shader(err)
     }
 }
@@ -41,18 +46,23 @@ pub struct Screen {
     size: Size,
 
     pub wc: WindowedContext<PossiblyCurrent>,
-    pub shader: shader::TextShader,
+    pub shader: text::TextShader,
+
+    shaper: Shaper,
+
+    /// Set via [`Screen::enable_live_reload`]; polled once per redraw.
+    live_reload: Option<text::LiveReloadHandle>,
 }
 
 impl Screen {
-    pub fn new(el: &EventLoop<()>, font_family: &str, font_size: i32) -> Result<Screen, Error> {
+    pub fn new<T>(el: &EventLoop<T>, font_family: &str, font_size: i32) -> Result<Screen, Error> {
         let title = String::from("Peppa");
         let wb = WindowBuilder::new();
         let wc = ContextBuilder::new().build_windowed(wb, el)?;
         let wc = unsafe { wc.make_current().unwrap() };
         let win = wc.window();
 
-        shader::setup_opengl(|symbol| wc.get_proc_address(symbol) as *const _);
+        text::setup_opengl(|symbol| wc.get_proc_address(symbol) as *const _);
 
         win.set_title(title.as_str());
         win.set_cursor_icon(CursorIcon::Text);
@@ -66,7 +76,8 @@ impl Screen {
         let dpr = win.current_monitor().scale_factor();
         info!("Device pixel ratio: {}", dpr);
 
-        let shader = shader::TextShader::new(dpr as _, font_family, font_size)?;
+        let shader = text::TextShader::new(dpr as _, font_family, font_size)?;
+        let shaper = Shaper::new(font::resolve_font_path(font_family).as_deref());
         let size = Size {
             lines: 25,
             columns: 80,
@@ -77,9 +88,32 @@ impl Screen {
             wc,
             size,
             shader,
+            shaper,
+            live_reload: None,
         })
     }
 
+    /// Watch the active shader/font files and hot-swap them when they change
+    /// on disk, for fast iteration without restarting Peppa. Opt-in: call
+    /// [`Screen::poll_live_reload`] once per redraw for it to take effect.
+    pub fn enable_live_reload(&mut self) -> Result<(), Error> {
+        self.live_reload = Some(self.shader.enable_live_reload()?);
+        Ok(())
+    }
+
+    /// Apply any shader/font reload the live-reload watcher has debounced
+    /// since the last call, recomputing terminal size in case cell metrics
+    /// changed. A no-op if live reload was never enabled.
+    pub fn poll_live_reload(&mut self) {
+        if let Some(handle) = &self.live_reload {
+            match handle.poll(&mut self.shader) {
+                Ok(true) => self.resize(),
+                Ok(false) => {}
+                Err(err) => error!("live reload failed: {:?}", err),
+            }
+        }
+    }
+
     pub fn set_title(&mut self, title: &str) {
         self.title = String::from(title);
         self.wc.window().set_title(title);
@@ -98,8 +132,8 @@ impl Screen {
         let window_size = self.wc.window().inner_size();
 
         let size = Size {
-            columns: (window_size.width as f32 / self.shader.cell_width).floor() as usize,
-            lines: (window_size.height as f32 / self.shader.cell_height).floor() as usize,
+            columns: (window_size.width as f32 / self.shader.cell_width()).floor() as usize,
+            lines: (window_size.height as f32 / self.shader.cell_height()).floor() as usize,
         };
 
         info!(
@@ -121,13 +155,144 @@ impl Screen {
         self.wc.swap_buffers().unwrap();
     }
 
-    pub fn set_line(&mut self, row: usize, s: &str) {
-        if row >= self.size.lines {
-            return;
+    /// The current terminal size, in cells.
+    pub fn size(&self) -> &Size {
+        &self.size
+    }
+
+    /// Ask the windowing system to schedule a `RedrawRequested` event.
+    pub fn request_redraw(&self) {
+        self.wc.window().request_redraw();
+    }
+
+    /// Rebuild the rasterizer and cell metrics for a new device pixel ratio, then
+    /// recompute the terminal size against the (already-physical) window size.
+    ///
+    /// Must be called with the physical size that accompanies the DPI change, since
+    /// winit can otherwise deliver a `Resized` event carrying the stale DPI first.
+    pub fn set_scale_factor(
+        &mut self,
+        dpr: f32,
+        new_inner_size: PhysicalSize<u32>,
+    ) -> Result<(), Error> {
+        self.shader.set_scale_factor(dpr)?;
+
+        let size = Size {
+            columns: (new_inner_size.width as f32 / self.shader.cell_width()).floor() as usize,
+            lines: (new_inner_size.height as f32 / self.shader.cell_height()).floor() as usize,
+        };
+
+        self.wc.resize(new_inner_size);
+        self.shader.resize(new_inner_size.width, new_inner_size.height);
+        self.shader.set_size(size.lines, size.columns);
+        self.size = size;
+        self.wc.window().request_redraw();
+
+        Ok(())
+    }
+
+    /// Paint every cell of `grid` onto the screen, shaping each row's run
+    /// through `self.shaper` first so ligatures, combining marks, and wide
+    /// CJK/emoji glyphs land on the right columns instead of one-char-per-cell.
+    pub fn set_grid(&mut self, grid: &TermGrid) {
+        for row in 0..self.size.lines.min(grid.lines) {
+            let columns = self.size.columns.min(grid.columns);
+            let line: String = (0..columns).map(|col| grid.cell(row, col).ch).collect();
+
+            for (col, src_col, shaped) in plan_row(&self.shaper, &line, columns) {
+                let cell = grid.cell(row, src_col);
+                let mut flags = CellFlags::empty();
+                flags.set(CellFlags::BOLD, cell.bold);
+                flags.set(CellFlags::ITALIC, cell.italic);
+
+                let fg = cell.fg.to_rgb(DEFAULT_FG);
+                let bg = cell.bg.to_rgb(DEFAULT_BG);
+
+                // Only one decoration rect is drawn per cell, so a strikethrough
+                // set alongside an underline is dropped in favour of the underline; the combination
+                // is rare enough in real shell output not to be worth two passes.
+                let decoration = match cell.underline {
+                    Underline::None if cell.strikethrough => Decoration::Strikethrough,
+                    Underline::None => Decoration::None,
+                    Underline::Single => Decoration::Underline,
+                    Underline::Double => Decoration::DoubleUnderline,
+                    Underline::Curly => Decoration::Undercurl,
+                    Underline::Dotted => Decoration::Dotted,
+                    Underline::Dashed => Decoration::Dashed,
+                };
+
+                self.shader.set_text(row, col, shaped.ch, flags, fg);
+                self.shader.set_background(row, col, bg);
+                self.shader.set_decoration(row, col, decoration, fg);
+
+                // A wide glyph leaves the remaining destination columns blank
+                // so stale glyphs don't linger there.
+                for extra in 1..shaped.columns {
+                    let fill_col = col + extra;
+                    if fill_col >= columns {
+                        break;
+                    }
+                    self.shader.set_text(row, fill_col, ' ', CellFlags::empty(), fg);
+                    self.shader.set_background(row, fill_col, bg);
+                    self.shader.set_decoration(row, fill_col, Decoration::None, fg);
+                }
+            }
         }
+    }
+}
 
-        for (i, ch) in s.chars().take(self.size.columns).enumerate() {
-            self.shader.set_text(row, i, ch);
+/// Pairs each of `shaper.shape_line(line)`'s cells with the destination column
+/// to paint it at and the source `TermGrid` column to read its style from.
+/// Split out from `set_grid` so the column bookkeeping can be unit tested
+/// without a live GL context.
+///
+/// `col` (destination) advances by `shaped.columns`, so a wide glyph's filler
+/// columns are skipped over; `src_col` (source) advances by `shaped.src_chars`,
+/// so source columns folded into a cluster (combining marks, merged cluster
+/// components) aren't read again as separate cells. The two drift apart
+/// whenever a cluster's `columns` and `src_chars` differ, which is exactly why
+/// they can't share one cursor.
+fn plan_row(shaper: &Shaper, line: &str, columns: usize) -> Vec<(usize, usize, ShapedCell)> {
+    let mut plan = Vec::new();
+    let (mut col, mut src_col) = (0, 0);
+    for shaped in shaper.shape_line(line) {
+        if col >= columns || src_col >= columns {
+            break;
         }
+        plan.push((col, src_col, shaped));
+        col += shaped.columns;
+        src_col += shaped.src_chars;
     }
+    plan
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_glyph_then_ascii_keeps_destination_and_source_columns_apart() {
+        // No font path, so `shape_line` falls back to one cluster per char:
+        // '中' is double-width (columns=2, src_chars=1), 'x' is plain ASCII
+        // (columns=1, src_chars=1) — enough to make `col` and `src_col` drift
+        // apart without needing a real font file to shape.
+        let shaper = Shaper::new(None);
+        let plan = plan_row(&shaper, "中x", 10);
+
+        assert_eq!(plan.len(), 2);
+        assert_eq!((plan[0].0, plan[0].1, plan[0].2.ch), (0, 0, '中'));
+        assert_eq!((plan[1].0, plan[1].1, plan[1].2.ch), (2, 1, 'x'));
+    }
+
+    #[test]
+    fn plan_stops_once_destination_columns_run_out() {
+        let shaper = Shaper::new(None);
+        let plan = plan_row(&shaper, "abc", 2);
+        assert_eq!(plan.len(), 2);
+    }
+}
+
+/// Default foreground/background used for cells left at `TermColor::Default`,
+/// i.e. the usual light-on-dark terminal convention.
+const DEFAULT_FG: [f32; 3] = [1.0, 1.0, 1.0];
+const DEFAULT_BG: [f32; 3] = [0.0, 0.0, 0.0];
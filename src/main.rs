@@ -6,22 +6,43 @@
 
 mod font;
 mod gui;
-mod shader;
+mod parser;
+mod pty;
+mod shape;
+mod text;
 
 use {
-    crate::gui::{Screen, Size},
+    crate::{
+        gui::{Screen, Size},
+        parser::{Parser, TermGrid},
+        pty::Pty,
+        text::Renderer,
+    },
     glutin::{
         dpi::PhysicalSize,
         event::{ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent},
-        event_loop::{ControlFlow, EventLoop},
+        event_loop::{ControlFlow, EventLoop, EventLoopProxy},
+    },
+    log::{error, info},
+    std::{
+        env,
+        io::{Read, Write},
+        sync::{Arc, Mutex},
+        thread,
     },
-    log::info,
-    std::env,
 };
 
+/// Events sent from the PTY worker thread back to the renderer's event loop.
+#[derive(Debug)]
+enum PeppaEvent {
+    /// New terminal output is available; request a redraw.
+    Wakeup,
+}
+
 #[derive(Debug)]
 enum Error {
     Gui(gui::Error),
+    Pty(pty::Error),
 }
 
 impl From<gui::Error> for Error {
@@ -30,19 +51,25 @@ impl From<gui::Error> for Error {
     }
 }
 
+impl From<pty::Error> for Error {
+    fn from(err: pty::Error) -> Self {
+        Error::Pty(err)
+    }
+}
+
 fn main() -> Result<(), Error> {
     pretty_env_logger::init();
 
     let args: Vec<String> = env::args().collect();
-    if args.len() < 4 {
-        println!("Usage: {} <font> <size> <string1> [<stringN> ...]", args[0]);
+    if args.len() < 3 {
+        println!("Usage: {} <font> <size>", args[0]);
         return Ok(());
     }
 
     let font_family = &args[1];
     let font_size = &args[2];
 
-    let el = EventLoop::new();
+    let el = EventLoop::<PeppaEvent>::with_user_event();
     let (size, dpr) = el
         .available_monitors()
         .next()
@@ -64,20 +91,98 @@ fn main() -> Result<(), Error> {
     screen.set_title("Peppa");
     screen.resize();
 
-    redraw(&mut screen);
+    // Opt-in: PEPPA_LIVE_RELOAD=1 watches the shader/font files and hot-swaps
+    // them on change, for iterating without restarting Peppa.
+    if env::var_os("PEPPA_LIVE_RELOAD").is_some() {
+        if let Err(err) = screen.enable_live_reload() {
+            error!("failed to enable live reload: {:?}", err);
+        }
+    }
+
+    let term_size = screen.size();
+    let pty = Pty::spawn(term_size.columns as u16, term_size.lines as u16)?;
+    let grid = Arc::new(Mutex::new(TermGrid::new(term_size.lines, term_size.columns)));
+
+    let reader = pty.reader().expect("pty reader");
+    let writer = pty.writer().expect("pty writer");
+    spawn_pty_worker(reader, Arc::clone(&grid), el.create_proxy());
 
-    run(screen, el);
+    run(screen, el, pty, writer, grid);
 
     Ok(())
 }
 
-fn run(mut screen: Screen, el: EventLoop<()>) {
+/// Own PTY reads and VT parsing on a dedicated thread, so a slow or idle shell never
+/// stalls the glutin event loop. The renderer only ever reads `grid` through the lock.
+fn spawn_pty_worker(
+    mut reader: std::fs::File,
+    grid: Arc<Mutex<TermGrid>>,
+    proxy: EventLoopProxy<PeppaEvent>,
+) {
+    thread::spawn(move || {
+        let mut parser = Parser::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    {
+                        let mut grid = grid.lock().unwrap();
+                        for &byte in &buf[..n] {
+                            parser.advance(&mut grid, byte);
+                        }
+                    }
+                    if proxy.send_event(PeppaEvent::Wakeup).is_err() {
+                        // The event loop is gone; nothing left to wake up.
+                        break;
+                    }
+                }
+                Err(err) => {
+                    error!("failed to read from pty: {}", err);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+fn run(
+    mut screen: Screen,
+    el: EventLoop<PeppaEvent>,
+    pty: Pty,
+    mut writer: std::fs::File,
+    grid: Arc<Mutex<TermGrid>>,
+) {
     let mut modifiers_state = Default::default();
+
     el.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
         match event {
             Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Resized(physical_size) => screen.resize(),
+                WindowEvent::Resized(_) => {
+                    screen.resize();
+                    let size = screen.size();
+                    grid.lock().unwrap().resize(size.lines, size.columns);
+                    if let Err(err) = pty.resize(size.columns as u16, size.lines as u16) {
+                        error!("failed to resize pty: {:?}", err);
+                    }
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    // Convert to physical units as soon as the event arrives: winit can
+                    // otherwise deliver a stale-DPI `Resized` event right behind this one.
+                    if let Err(err) = screen.set_scale_factor(scale_factor as f32, *new_inner_size)
+                    {
+                        error!("failed to apply new scale factor: {:?}", err);
+                    }
+                    let size = screen.size();
+                    grid.lock().unwrap().resize(size.lines, size.columns);
+                    if let Err(err) = pty.resize(size.columns as u16, size.lines as u16) {
+                        error!("failed to resize pty: {:?}", err);
+                    }
+                }
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                 WindowEvent::ModifiersChanged(state) => modifiers_state = state,
                 WindowEvent::KeyboardInput {
@@ -91,12 +196,130 @@ fn run(mut screen: Screen, el: EventLoop<()>) {
                 } => {
                     if modifiers_state.logo() {
                         screen.toggle_fullscreen();
+                    } else if let Err(err) = writer.write_all(b"\r") {
+                        error!("failed to write to pty: {}", err);
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Err(err) = writer.write_all(b"\x1b") {
+                        error!("failed to write to pty: {}", err);
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Back),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Err(err) = writer.write_all(b"\x7f") {
+                        error!("failed to write to pty: {}", err);
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Tab),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Err(err) = writer.write_all(b"\t") {
+                        error!("failed to write to pty: {}", err);
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode:
+                                Some(
+                                    keycode @ (VirtualKeyCode::Up
+                                    | VirtualKeyCode::Down
+                                    | VirtualKeyCode::Right
+                                    | VirtualKeyCode::Left),
+                                ),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    let seq: &[u8] = match keycode {
+                        VirtualKeyCode::Up => b"\x1b[A",
+                        VirtualKeyCode::Down => b"\x1b[B",
+                        VirtualKeyCode::Right => b"\x1b[C",
+                        VirtualKeyCode::Left => b"\x1b[D",
+                        _ => unreachable!(),
+                    };
+                    if let Err(err) = writer.write_all(seq) {
+                        error!("failed to write to pty: {}", err);
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(keycode @ (VirtualKeyCode::PageUp | VirtualKeyCode::PageDown)),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if modifiers_state.shift() => {
+                    let pages = if keycode == VirtualKeyCode::PageUp { 1 } else { -1 };
+                    grid.lock().unwrap().scroll_pages(pages);
+                    screen.request_redraw();
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(keycode),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if modifiers_state.ctrl() => {
+                    if let Some(code) = ctrl_code(keycode) {
+                        if let Err(err) = writer.write_all(&[code]) {
+                            error!("failed to write to pty: {}", err);
+                        }
+                    }
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let lines = match delta {
+                        glutin::event::MouseScrollDelta::LineDelta(_, y) => y as isize,
+                        glutin::event::MouseScrollDelta::PixelDelta(pos) => {
+                            (pos.y / screen.shader.cell_height() as f64) as isize
+                        }
+                    };
+                    if lines != 0 {
+                        grid.lock().unwrap().scroll(lines);
+                        screen.request_redraw();
+                    }
+                }
+                // Backspace/Tab/arrows/Escape/Ctrl+letter are all already sent above
+                // via their virtual_keycode; without this filter they'd double-send
+                // as their raw control character here too.
+                WindowEvent::ReceivedCharacter(ch) if !ch.is_control() => {
+                    let mut buf = [0u8; 4];
+                    if let Err(err) = writer.write_all(ch.encode_utf8(&mut buf).as_bytes()) {
+                        error!("failed to write to pty: {}", err);
                     }
                 }
                 _ => (),
             },
+            Event::UserEvent(PeppaEvent::Wakeup) => screen.request_redraw(),
             Event::RedrawRequested(_) => {
-                redraw(&mut screen);
+                screen.poll_live_reload();
+                screen.set_grid(&grid.lock().unwrap());
                 screen.draw_frame();
             }
             Event::LoopDestroyed => {}
@@ -105,11 +328,18 @@ fn run(mut screen: Screen, el: EventLoop<()>) {
     });
 }
 
-fn redraw(screen: &mut Screen) {
-    let args: Vec<String> = env::args().collect();
-    let strs = &args[3..];
-
-    for (i, s) in strs.iter().enumerate() {
-        screen.set_line(i, s);
-    }
+/// The control code a real terminal's line discipline would see for a
+/// held-Ctrl letter key, e.g. Ctrl+C -> ETX (0x03, usually SIGINTs the
+/// foreground process), Ctrl+D -> EOT (0x04, usually signals EOF). `None` for
+/// keys with no such mapping.
+fn ctrl_code(keycode: VirtualKeyCode) -> Option<u8> {
+    use VirtualKeyCode::*;
+    let letter = match keycode {
+        A => b'a', B => b'b', C => b'c', D => b'd', E => b'e', F => b'f', G => b'g', H => b'h',
+        I => b'i', J => b'j', K => b'k', L => b'l', M => b'm', N => b'n', O => b'o', P => b'p',
+        Q => b'q', R => b'r', S => b's', T => b't', U => b'u', V => b'v', W => b'w', X => b'x',
+        Y => b'y', Z => b'z',
+        _ => return None,
+    };
+    Some(letter & 0x1f)
 }
@@ -0,0 +1,557 @@
+//! A small VT100/ANSI escape-sequence state machine that drives an in-memory
+//! terminal grid, replacing the static strings `Screen::set_line` used to paint.
+
+use std::{collections::VecDeque, mem};
+
+/// How many scrolled-off rows `TermGrid` keeps around for scrollback.
+const SCROLLBACK_LIMIT: usize = 10_000;
+
+/// A cell's foreground color (`CSI 3x`/`CSI 9x m`, reset by `CSI 39 m`) or
+/// background color (`CSI 4x`/`CSI 10x m`, reset by `CSI 49 m`). Only the
+/// classic 16-color ANSI palette is supported; 256-color and truecolor SGR
+/// sequences are left unparsed for now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermColor {
+    Default,
+    Indexed(u8),
+}
+
+impl Default for TermColor {
+    fn default() -> Self {
+        TermColor::Default
+    }
+}
+
+impl TermColor {
+    /// Resolve to an RGB triple the renderer can feed straight into a shader,
+    /// using the classic 16-color ANSI palette. `default` is the color to fall
+    /// back to for `TermColor::Default`, so callers can pass their own default
+    /// foreground or background and keep the usual black-on-white-or-white-on-black convention.
+    pub fn to_rgb(self, default: [f32; 3]) -> [f32; 3] {
+        match self {
+            TermColor::Default => default,
+            TermColor::Indexed(i) => ANSI_PALETTE[(i as usize) % ANSI_PALETTE.len()],
+        }
+    }
+}
+
+/// The classic 16-color ANSI palette (black, red, green, yellow, blue,
+/// magenta, cyan, white, then their bright counterparts), indexed by the `n`
+/// in `CSI 3n/4n m` (0-7) or `CSI 9n/10n m` (8-15).
+const ANSI_PALETTE: [[f32; 3]; 16] = [
+    [0.0, 0.0, 0.0],
+    [0.80, 0.0, 0.0],
+    [0.0, 0.80, 0.0],
+    [0.80, 0.80, 0.0],
+    [0.0, 0.0, 0.80],
+    [0.80, 0.0, 0.80],
+    [0.0, 0.80, 0.80],
+    [0.80, 0.80, 0.80],
+    [0.40, 0.40, 0.40],
+    [1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [1.0, 0.0, 1.0],
+    [0.0, 1.0, 1.0],
+    [1.0, 1.0, 1.0],
+];
+
+/// Underline style set by `CSI 4 m` (single), `CSI 21 m` (double), or the
+/// colon sub-parameter form `CSI 4 : n m` (curly/dotted/dashed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Underline {
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+impl Default for Underline {
+    fn default() -> Self {
+        Underline::None
+    }
+}
+
+/// A single cell of the terminal grid.
+#[derive(Debug, Clone, Copy)]
+pub struct TermCell {
+    pub ch: char,
+    pub bold: bool,
+    pub italic: bool,
+    pub fg: TermColor,
+    pub bg: TermColor,
+    pub underline: Underline,
+    pub strikethrough: bool,
+}
+
+impl Default for TermCell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            bold: false,
+            italic: false,
+            fg: TermColor::Default,
+            bg: TermColor::Default,
+            underline: Underline::None,
+            strikethrough: false,
+        }
+    }
+}
+
+/// SGR text attributes accumulated by `CSI ... m` and applied to cells as they're
+/// written, until reset (`CSI 0 m`) or changed again.
+#[derive(Debug, Clone, Copy, Default)]
+struct GraphicAttrs {
+    bold: bool,
+    italic: bool,
+    fg: TermColor,
+    bg: TermColor,
+    underline: Underline,
+    strikethrough: bool,
+}
+
+/// The terminal's character grid plus cursor position.
+pub struct TermGrid {
+    pub lines: usize,
+    pub columns: usize,
+    pub cursor_row: usize,
+    pub cursor_col: usize,
+    cells: Vec<Vec<TermCell>>,
+    attrs: GraphicAttrs,
+
+    /// Rows that have scrolled off the top of the viewport, oldest first, bounded
+    /// to `SCROLLBACK_LIMIT` as a ring buffer (old rows drop off the front).
+    scrollback: VecDeque<Vec<TermCell>>,
+    /// How many rows back the view is currently scrolled; 0 means "live".
+    view_offset: usize,
+}
+
+impl TermGrid {
+    pub fn new(lines: usize, columns: usize) -> Self {
+        Self {
+            lines,
+            columns,
+            cursor_row: 0,
+            cursor_col: 0,
+            cells: vec![vec![TermCell::default(); columns]; lines],
+            attrs: GraphicAttrs::default(),
+            scrollback: VecDeque::new(),
+            view_offset: 0,
+        }
+    }
+
+    pub fn resize(&mut self, lines: usize, columns: usize) {
+        self.cells.resize(lines, vec![TermCell::default(); columns]);
+        for row in &mut self.cells {
+            row.resize(columns, TermCell::default());
+        }
+        self.lines = lines;
+        self.columns = columns;
+        self.cursor_row = self.cursor_row.min(lines.saturating_sub(1));
+        self.cursor_col = self.cursor_col.min(columns.saturating_sub(1));
+        self.view_offset = self.view_offset.min(self.scrollback.len());
+    }
+
+    /// Read a cell from the currently visible viewport, which may be scrolled back
+    /// into history via [`TermGrid::scroll`].
+    pub fn cell(&self, row: usize, col: usize) -> TermCell {
+        if self.view_offset == 0 {
+            return self.cells[row][col];
+        }
+
+        let start = self.scrollback.len().saturating_sub(self.view_offset);
+        let scrollback_rows_shown = self.scrollback.len() - start;
+
+        if row < scrollback_rows_shown {
+            return self
+                .scrollback
+                .get(start + row)
+                .and_then(|row| row.get(col))
+                .copied()
+                .unwrap_or_default();
+        }
+
+        let live_row = row - scrollback_rows_shown;
+        self.cells
+            .get(live_row)
+            .and_then(|row| row.get(col))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Scroll the view by `delta` rows; positive moves further back into history.
+    /// Clamped to the available scrollback.
+    pub fn scroll(&mut self, delta: isize) {
+        let max = self.scrollback.len();
+        let offset = (self.view_offset as isize + delta).clamp(0, max as isize);
+        self.view_offset = offset as usize;
+    }
+
+    /// Scroll by whole pages (`self.lines` rows), as driven by Shift+PageUp/PageDown.
+    pub fn scroll_pages(&mut self, pages: isize) {
+        self.scroll(pages * self.lines as isize);
+    }
+
+    fn put(&mut self, ch: char) {
+        if self.cursor_col >= self.columns {
+            self.carriage_return();
+            self.newline();
+        }
+        self.cells[self.cursor_row][self.cursor_col] = TermCell {
+            ch,
+            bold: self.attrs.bold,
+            italic: self.attrs.italic,
+            fg: self.attrs.fg,
+            bg: self.attrs.bg,
+            underline: self.attrs.underline,
+            strikethrough: self.attrs.strikethrough,
+        };
+        self.cursor_col += 1;
+    }
+
+    /// Apply a `CSI ... m` (SGR) parameter, as dispatched by `Parser`. `sub` holds
+    /// any colon sub-parameters attached to this one (e.g. the `3` in `4:3`).
+    fn set_graphic_attr(&mut self, param: u16, sub: &[u16]) {
+        match param {
+            0 => self.attrs = GraphicAttrs::default(),
+            1 => self.attrs.bold = true,
+            3 => self.attrs.italic = true,
+            4 => {
+                self.attrs.underline = match sub.first() {
+                    Some(3) => Underline::Curly,
+                    Some(4) => Underline::Dotted,
+                    Some(5) => Underline::Dashed,
+                    _ => Underline::Single,
+                };
+            }
+            9 => self.attrs.strikethrough = true,
+            21 => self.attrs.underline = Underline::Double,
+            22 => self.attrs.bold = false,
+            23 => self.attrs.italic = false,
+            24 => self.attrs.underline = Underline::None,
+            29 => self.attrs.strikethrough = false,
+            30..=37 => self.attrs.fg = TermColor::Indexed((param - 30) as u8),
+            39 => self.attrs.fg = TermColor::Default,
+            40..=47 => self.attrs.bg = TermColor::Indexed((param - 40) as u8),
+            49 => self.attrs.bg = TermColor::Default,
+            90..=97 => self.attrs.fg = TermColor::Indexed((param - 90 + 8) as u8),
+            100..=107 => self.attrs.bg = TermColor::Indexed((param - 100 + 8) as u8),
+            _ => {}
+        }
+    }
+
+    fn newline(&mut self) {
+        if self.cursor_row + 1 >= self.lines {
+            let scrolled_off = self.cells.remove(0);
+            self.scrollback.push_back(scrolled_off);
+            if self.scrollback.len() > SCROLLBACK_LIMIT {
+                self.scrollback.pop_front();
+            }
+            self.cells.push(vec![TermCell::default(); self.columns]);
+        } else {
+            self.cursor_row += 1;
+        }
+    }
+
+    fn carriage_return(&mut self) {
+        self.cursor_col = 0;
+    }
+
+    fn backspace(&mut self) {
+        self.cursor_col = self.cursor_col.saturating_sub(1);
+    }
+
+    fn move_cursor_to(&mut self, row: usize, col: usize) {
+        self.cursor_row = row.min(self.lines.saturating_sub(1));
+        self.cursor_col = col.min(self.columns.saturating_sub(1));
+    }
+
+    fn move_cursor_by(&mut self, rows: isize, cols: isize) {
+        let row = (self.cursor_row as isize + rows).clamp(0, self.lines as isize - 1);
+        let col = (self.cursor_col as isize + cols).clamp(0, self.columns as isize - 1);
+        self.cursor_row = row as usize;
+        self.cursor_col = col as usize;
+    }
+
+    /// `CSI n J` - erase in display.
+    fn erase_in_display(&mut self, mode: u16) {
+        match mode {
+            0 => {
+                for col in self.cursor_col..self.columns {
+                    self.cells[self.cursor_row][col] = TermCell::default();
+                }
+                for row in (self.cursor_row + 1)..self.lines {
+                    self.cells[row] = vec![TermCell::default(); self.columns];
+                }
+            }
+            1 => {
+                for row in 0..self.cursor_row {
+                    self.cells[row] = vec![TermCell::default(); self.columns];
+                }
+                for col in 0..=self.cursor_col.min(self.columns.saturating_sub(1)) {
+                    self.cells[self.cursor_row][col] = TermCell::default();
+                }
+            }
+            _ => {
+                self.cells = vec![vec![TermCell::default(); self.columns]; self.lines];
+            }
+        }
+    }
+
+    /// `CSI n K` - erase in line.
+    fn erase_in_line(&mut self, mode: u16) {
+        let row = &mut self.cells[self.cursor_row];
+        match mode {
+            0 => row[self.cursor_col..].fill(TermCell::default()),
+            1 => row[..=self.cursor_col.min(row.len() - 1)].fill(TermCell::default()),
+            _ => row.fill(TermCell::default()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+    Osc,
+}
+
+/// Incremental VT100/ANSI parser: feed it bytes as they arrive from the PTY and it
+/// updates a `TermGrid` in place.
+pub struct Parser {
+    state: State,
+    params: Vec<u16>,
+    /// Colon-separated sub-parameters for each entry in `params` (e.g. `4:3`
+    /// records `params = [4]`, `subparams = [[3]]`), as opposed to `;`, which
+    /// starts a new unrelated main parameter.
+    subparams: Vec<Vec<u16>>,
+    current: String,
+    current_subparams: Vec<u16>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: Vec::new(),
+            subparams: Vec::new(),
+            current: String::new(),
+            current_subparams: Vec::new(),
+        }
+    }
+
+    /// Feed a single byte read from the PTY into the state machine.
+    pub fn advance(&mut self, grid: &mut TermGrid, byte: u8) {
+        match self.state {
+            State::Ground => match byte {
+                0x1b => self.state = State::Escape,
+                b'\n' => grid.newline(),
+                b'\r' => grid.carriage_return(),
+                0x08 => grid.backspace(),
+                0x20..=0x7e => grid.put(byte as char),
+                _ => {}
+            },
+            State::Escape => match byte {
+                b'[' => {
+                    self.params.clear();
+                    self.subparams.clear();
+                    self.current.clear();
+                    self.current_subparams.clear();
+                    self.state = State::Csi;
+                }
+                b']' => {
+                    self.current.clear();
+                    self.state = State::Osc;
+                }
+                _ => self.state = State::Ground,
+            },
+            State::Csi => match byte {
+                b'0'..=b'9' => self.current.push(byte as char),
+                b';' => self.push_param(),
+                b':' => self.push_subparam(),
+                0x40..=0x7e => {
+                    self.push_param();
+                    self.dispatch_csi(grid, byte as char);
+                    self.state = State::Ground;
+                }
+                _ => {}
+            },
+            // OSC sequences (e.g. window title) are consumed and discarded; Peppa has
+            // no title bar driven by the shell yet.
+            State::Osc => match byte {
+                0x07 => self.state = State::Ground,
+                b'\\' if self.current.ends_with('\x1b') => self.state = State::Ground,
+                _ => self.current.push(byte as char),
+            },
+        }
+    }
+
+    fn push_param(&mut self) {
+        let value = self.current.parse().unwrap_or(0);
+        let mut subparams = mem::take(&mut self.current_subparams);
+        // After a `:`, `push_subparam` has already stashed the main parameter as
+        // the first entry, and `value` is the last sub-parameter.
+        let main = if subparams.is_empty() {
+            value
+        } else {
+            subparams.push(value);
+            subparams.remove(0)
+        };
+        self.params.push(main);
+        self.subparams.push(subparams);
+        self.current.clear();
+    }
+
+    fn push_subparam(&mut self) {
+        let value = self.current.parse().unwrap_or(0);
+        self.current_subparams.push(value);
+        self.current.clear();
+    }
+
+    fn param(&self, idx: usize, default: u16) -> u16 {
+        match self.params.get(idx) {
+            Some(0) | None => default,
+            Some(&v) => v,
+        }
+    }
+
+    fn dispatch_csi(&mut self, grid: &mut TermGrid, final_byte: char) {
+        match final_byte {
+            'H' | 'f' => {
+                let row = self.param(0, 1).saturating_sub(1) as usize;
+                let col = self.param(1, 1).saturating_sub(1) as usize;
+                grid.move_cursor_to(row, col);
+            }
+            'A' => grid.move_cursor_by(-(self.param(0, 1) as isize), 0),
+            'B' => grid.move_cursor_by(self.param(0, 1) as isize, 0),
+            'C' => grid.move_cursor_by(0, self.param(0, 1) as isize),
+            'D' => grid.move_cursor_by(0, -(self.param(0, 1) as isize)),
+            'J' => grid.erase_in_display(self.param(0, 0)),
+            'K' => grid.erase_in_line(self.param(0, 0)),
+            'm' => {
+                if self.params.is_empty() {
+                    grid.set_graphic_attr(0, &[]);
+                } else {
+                    for (i, &param) in self.params.iter().enumerate() {
+                        grid.set_graphic_attr(param, &self.subparams[i]);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(bytes: &[u8]) -> (Parser, TermGrid) {
+        let mut parser = Parser::new();
+        let mut grid = TermGrid::new(5, 10);
+        for &byte in bytes {
+            parser.advance(&mut grid, byte);
+        }
+        (parser, grid)
+    }
+
+    #[test]
+    fn csi_h_moves_cursor_to_one_indexed_row_col() {
+        let (_, grid) = feed(b"\x1b[3;5H");
+        assert_eq!((grid.cursor_row, grid.cursor_col), (2, 4));
+    }
+
+    #[test]
+    fn csi_h_with_no_params_defaults_to_home() {
+        let (_, grid) = feed(b"\x1b[5;5H\x1b[H");
+        assert_eq!((grid.cursor_row, grid.cursor_col), (0, 0));
+    }
+
+    #[test]
+    fn csi_cursor_movement_is_clamped_to_the_grid() {
+        let (_, grid) = feed(b"\x1b[100A");
+        assert_eq!(grid.cursor_row, 0);
+    }
+
+    #[test]
+    fn sgr_sets_and_resets_bold() {
+        let (_, mut grid) = feed(b"\x1b[1m");
+        grid.put('x');
+        assert!(grid.cell(0, 0).bold);
+
+        let (_, mut grid) = feed(b"\x1b[1m\x1b[0m");
+        grid.put('x');
+        assert!(!grid.cell(0, 0).bold);
+    }
+
+    #[test]
+    fn sgr_sets_indexed_foreground_and_background() {
+        let (_, mut grid) = feed(b"\x1b[31;44m");
+        grid.put('x');
+        let cell = grid.cell(0, 0);
+        assert_eq!(cell.fg, TermColor::Indexed(1));
+        assert_eq!(cell.bg, TermColor::Indexed(4));
+    }
+
+    #[test]
+    fn sgr_colon_subparam_selects_underline_style() {
+        let (_, mut grid) = feed(b"\x1b[4:3m");
+        grid.put('x');
+        assert_eq!(grid.cell(0, 0).underline, Underline::Curly);
+    }
+
+    /// A 3-row grid with 4 rows of distinct markers pushed through it, so two
+    /// rows ('0', '1') have scrolled off into scrollback and the live viewport
+    /// holds '2', '3', and the empty row the last newline left behind.
+    fn grid_with_scrollback() -> TermGrid {
+        let mut grid = TermGrid::new(3, 4);
+        for row in 0..4 {
+            grid.put((b'0' + row) as char);
+            grid.newline();
+            grid.carriage_return();
+        }
+        grid
+    }
+
+    #[test]
+    fn cell_reads_the_live_grid_when_not_scrolled() {
+        let grid = grid_with_scrollback();
+        assert_eq!(grid.cell(0, 0).ch, '2');
+        assert_eq!(grid.cell(1, 0).ch, '3');
+    }
+
+    #[test]
+    fn scroll_shifts_the_viewport_into_scrollback() {
+        let mut grid = grid_with_scrollback();
+        grid.scroll(1);
+        assert_eq!(grid.cell(0, 0).ch, '1');
+        assert_eq!(grid.cell(1, 0).ch, '2');
+        assert_eq!(grid.cell(2, 0).ch, '3');
+    }
+
+    #[test]
+    fn scroll_to_the_oldest_row_shows_full_history() {
+        let mut grid = grid_with_scrollback();
+        grid.scroll(2);
+        assert_eq!(grid.cell(0, 0).ch, '0');
+        assert_eq!(grid.cell(1, 0).ch, '1');
+        assert_eq!(grid.cell(2, 0).ch, '2');
+    }
+
+    #[test]
+    fn scroll_is_clamped_to_the_available_scrollback() {
+        let mut grid = grid_with_scrollback();
+        grid.scroll(100);
+        assert_eq!(grid.cell(0, 0).ch, '0');
+    }
+}
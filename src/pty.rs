@@ -0,0 +1,156 @@
+//! Spawns a child shell behind a pseudo-terminal so Peppa has something to render.
+
+use {
+    log::error,
+    nix::pty::{openpty, Winsize},
+    std::{
+        env,
+        fs::File,
+        io,
+        os::unix::{
+            io::{AsRawFd, FromRawFd},
+            process::CommandExt,
+        },
+        process::{Child, Command, Stdio},
+    },
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Nix(nix::Error),
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<nix::Error> for Error {
+    fn from(err: nix::Error) -> Self {
+        Self::Nix(err)
+    }
+}
+
+/// A PTY-backed child process, plus the master side of the pair used to talk to it.
+pub struct Pty {
+    child: Child,
+    master: File,
+}
+
+impl Pty {
+    /// Open a new PTY sized to `columns`x`lines` and spawn the user's shell on the slave side.
+    pub fn spawn(columns: u16, lines: u16) -> Result<Pty, Error> {
+        let winsize = Winsize {
+            ws_row: lines,
+            ws_col: columns,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        let ends = openpty(Some(&winsize), None)?;
+        let (master, slave) = (ends.master, ends.slave);
+
+        // Open the slave fd once and `try_clone` it for the other two standard
+        // streams (as `Pty::reader`/`writer` already do for `master`), rather than
+        // wrapping the same raw fd in three separate `Stdio`s: each owns and closes
+        // its fd number on drop, so three wrappers around one fd meant this fd
+        // number got closed up to three times over. If another thread opened an
+        // unrelated fd in between and got the same number back, a later close here
+        // would silently tear that fd down instead.
+        let slave = unsafe { File::from_raw_fd(slave) };
+        let stdout = slave.try_clone()?;
+        let stderr = slave.try_clone()?;
+
+        let shell = default_shell();
+        let mut builder = Command::new(&shell);
+        builder
+            .stdin(Stdio::from(slave))
+            .stdout(Stdio::from(stdout))
+            .stderr(Stdio::from(stderr));
+
+        unsafe {
+            builder.pre_exec(move || {
+                nix::unistd::setsid().map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let child = builder.spawn()?;
+
+        let master = unsafe { File::from_raw_fd(master) };
+
+        Ok(Pty { child, master })
+    }
+
+    /// A handle for reading PTY output, independent of the writer handle.
+    pub fn reader(&self) -> io::Result<File> {
+        self.master.try_clone()
+    }
+
+    /// A handle for writing keyboard input back to the child.
+    pub fn writer(&self) -> io::Result<File> {
+        self.master.try_clone()
+    }
+
+    /// Notify the child that the window size changed.
+    pub fn resize(&self, columns: u16, lines: u16) -> Result<(), Error> {
+        let winsize = Winsize {
+            ws_row: lines,
+            ws_col: columns,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        unsafe {
+            if libc::ioctl(
+                self.master.as_raw_fd(),
+                libc::TIOCSWINSZ as _,
+                &winsize as *const Winsize,
+            ) < 0
+            {
+                return Err(Error::Io(io::Error::last_os_error()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the child shell has exited.
+    pub fn has_exited(&mut self) -> bool {
+        match self.child.try_wait() {
+            Ok(status) => status.is_some(),
+            Err(err) => {
+                error!("failed to poll child shell: {}", err);
+                true
+            }
+        }
+    }
+}
+
+/// Shell to spawn on the slave side when none is configured.
+#[cfg(target_os = "macos")]
+fn default_shell() -> String {
+    // macOS doesn't reliably set $SHELL to the user's configured login shell, so ask
+    // `dscl` instead, mirroring the macOS-specific PTY path Alacritty added when it
+    // moved font/PTY work off the main thread.
+    use std::process::Command as OsCommand;
+
+    let user = env::var("USER").unwrap_or_default();
+    OsCommand::new("dscl")
+        .args(&[".", "-read", &format!("/Users/{}", user), "UserShell"])
+        .output()
+        .ok()
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .and_then(|out| out.split_whitespace().nth(1).map(String::from))
+        .unwrap_or_else(|| String::from("/bin/bash"))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn default_shell() -> String {
+    env::var("SHELL").unwrap_or_else(|_| String::from("/bin/bash"))
+}
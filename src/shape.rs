@@ -0,0 +1,130 @@
+//! Shapes a line of text into cell-width clusters with rustybuzz, instead of
+//! assuming every `char` maps to exactly one grid column.
+//!
+//! Ligatures, combining marks, and wide CJK/emoji glyphs all break that one-to-one
+//! mapping. The whole line is shaped as a single rustybuzz buffer; it is not split
+//! into runs by font, script or direction. Each surviving cluster's width in
+//! columns comes from the Unicode width of the characters it covers, not from the
+//! glyph advances, which are only used to spot zero-advance combining marks. The
+//! shaped glyph IDs are not passed on: each cell carries its cluster's base `char`.
+
+use {
+    log::error,
+    rustybuzz::{Face, UnicodeBuffer},
+    std::{fs, path::Path},
+    unicode_width::UnicodeWidthChar,
+};
+
+/// A glyph placed at a particular cell, plus how many columns it occupies.
+#[derive(Debug, Clone, Copy)]
+pub struct ShapedCell {
+    /// The base character to rasterize. Combining marks belonging to this cluster
+    /// are folded in as the terminal grid doesn't yet support multi-codepoint cells.
+    pub ch: char,
+    /// Summed display width of the characters this cell folds in: 2 for wide
+    /// glyphs (most CJK, many emoji), 1 for a single narrow one, and one column
+    /// per merged narrow character for a ligature.
+    pub columns: usize,
+    /// How many source columns (one `TermGrid` column per input `char`) this
+    /// cell folds in — more than 1 when combining marks or merged cluster
+    /// components consumed extra source characters. Callers walking a line's
+    /// cells alongside its shaped output need two independent cursors: the
+    /// destination column advances by `columns` (so wide glyphs leave blank
+    /// filler cells behind them), while the cursor used to look up the source
+    /// `TermGrid` cell's style advances by `src_chars` (so folded-away source
+    /// columns aren't read again as separate cells).
+    pub src_chars: usize,
+}
+
+/// Shapes text against a loaded font face, falling back to a plain Unicode-width
+/// segmentation (one cell per `char`, no ligatures) when no face is available.
+pub struct Shaper {
+    face_data: Option<Vec<u8>>,
+}
+
+impl Shaper {
+    pub fn new(font_path: Option<&Path>) -> Self {
+        let face_data = font_path.and_then(|path| match fs::read(path) {
+            Ok(data) => Some(data),
+            Err(err) => {
+                error!("failed to read font file {}: {}", path.display(), err);
+                None
+            }
+        });
+
+        Self { face_data }
+    }
+
+    pub fn shape_line(&self, line: &str) -> Vec<ShapedCell> {
+        let face = self
+            .face_data
+            .as_deref()
+            .and_then(|data| Face::from_slice(data, 0));
+
+        match face {
+            Some(face) => shape_with_face(&face, line),
+            None => shape_by_width(line),
+        }
+    }
+}
+
+fn shape_with_face(face: &Face, line: &str) -> Vec<ShapedCell> {
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(line);
+    let output = rustybuzz::shape(face, &[], buffer);
+
+    let infos = output.glyph_infos();
+    let positions = output.glyph_positions();
+
+    let mut cells: Vec<ShapedCell> = Vec::with_capacity(infos.len());
+    let mut cluster_starts: Vec<usize> = Vec::with_capacity(infos.len());
+    for (info, pos) in infos.iter().zip(positions.iter()) {
+        let cluster = info.cluster as usize;
+        let ch = match line[cluster..].chars().next() {
+            Some(ch) => ch,
+            None => continue,
+        };
+
+        // Zero-advance glyphs are combining marks; stack them onto the base cell
+        // instead of opening a new column for them.
+        if pos.x_advance == 0 && !cells.is_empty() {
+            continue;
+        }
+
+        cells.push(ShapedCell {
+            ch,
+            columns: 1,
+            src_chars: 1,
+        });
+        cluster_starts.push(cluster);
+    }
+    cluster_starts.push(line.len());
+
+    // Now that every surviving cluster's start is known, each cell's `src_chars`
+    // is the number of source characters up to the next surviving cluster (or
+    // the end of the line) — covering any combining marks skipped above — and
+    // its `columns` is their summed display width, so a ligature keeps the
+    // columns of the characters it merged.
+    for (i, cell) in cells.iter_mut().enumerate() {
+        let span = &line[cluster_starts[i]..cluster_starts[i + 1]];
+        cell.src_chars = span.chars().count().max(1);
+        cell.columns = span
+            .chars()
+            .map(|c| UnicodeWidthChar::width(c).unwrap_or(0))
+            .sum::<usize>()
+            .max(1);
+    }
+
+    cells
+}
+
+/// Char-per-cell fallback used when the active font couldn't be shaped.
+fn shape_by_width(line: &str) -> Vec<ShapedCell> {
+    line.chars()
+        .map(|ch| ShapedCell {
+            ch,
+            columns: UnicodeWidthChar::width(ch).unwrap_or(1).max(1),
+            src_chars: 1,
+        })
+        .collect()
+}
@@ -0,0 +1,510 @@
+//! The primary renderer: every cell is a GPU instance, and the whole grid is
+//! drawn with one `glDrawElementsInstanced` call per draw flag and atlas
+//! texture rather than one per cell, relying on core GL 3.3 instancing
+//! (see [`super::supports_instancing`] for the capability check that selects
+//! this backend over [`super::vertex_fallback`]).
+
+use {
+    super::{
+        create_program, create_shader, CellFlags, CreationError, Decoration, GlyphCache,
+        RectRenderer, Renderer,
+    },
+    crate::font::{Font, FontKeys},
+    crossfont::{FontKey, GlyphKey},
+    gl::types::*,
+    log::{debug, error},
+    std::{borrow::Cow, mem},
+};
+
+use super::gl;
+
+static TEXT_SHADER_V_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.v.glsl");
+static TEXT_SHADER_F_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.f.glsl");
+
+static TEXT_SHADER_V: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.v.glsl"));
+static TEXT_SHADER_F: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.f.glsl"));
+
+pub struct Glsl3Renderer {
+    program: GLuint,
+    u_cell_size: GLint,
+    u_window_size: GLint,
+    u_draw_flag: GLint,
+
+    /// Renderer-owned instance buffer shared by every cell in the grid, so the
+    /// whole screen draws in one `DrawElementsInstanced` call per draw flag instead
+    /// of one per cell.
+    vao: GLuint,
+    vbo: GLuint,
+    ebo: GLuint,
+    /// CPU-side mirror of the VBO, indexed by `row * columns + col`.
+    instances: Vec<GlInstanceAttr>,
+    /// Atlas texture each `instances` entry's glyph was packed into, same
+    /// indexing as `instances`; used to group instances by atlas at draw time.
+    cell_atlas: Vec<GLuint>,
+    lines: usize,
+    columns: usize,
+
+    dpr: f32,
+    cell_width: f32,
+    cell_height: f32,
+    cell_descent: f32,
+
+    glyph_cache: GlyphCache,
+
+    /// Regular/bold/italic/bold-italic faces for the active font.
+    font_keys: FontKeys,
+
+    /// Draws cell backgrounds and text decorations (underline, strikethrough,
+    /// ...) as solid-color rects, around the batched glyph draw below.
+    rect_renderer: RectRenderer,
+}
+
+impl Glsl3Renderer {
+    pub fn new(dpr: f32, font_family: &str, font_size: i32) -> Result<Glsl3Renderer, CreationError> {
+        let vertex_shader = create_shader(gl::VERTEX_SHADER, TEXT_SHADER_V_PATH, TEXT_SHADER_V)?;
+        let fragment_shader =
+            create_shader(gl::FRAGMENT_SHADER, TEXT_SHADER_F_PATH, TEXT_SHADER_F)?;
+        let program = create_program(vertex_shader, fragment_shader)?;
+
+        let mut ft = Font::new(dpr, font_family, font_size);
+        let font_keys = ft.compute_font_keys()?;
+
+        let (cell_descent, cell_width, cell_height) =
+            Self::compute_cell_size(&mut ft, font_keys.regular)?;
+        debug!(
+            "cell_descent: {} cell_width: {} cell_height: {}",
+            cell_descent, cell_width, cell_height
+        );
+
+        let mut glyph_cache = GlyphCache::new(ft)?;
+        glyph_cache.prefill_ascii(font_keys.regular);
+
+        let (u_cell_size, u_window_size, u_draw_flag) = unsafe {
+            (
+                gl::GetUniformLocation(program, b"cellSize\0".as_ptr() as *const _),
+                gl::GetUniformLocation(program, b"windowSize\0".as_ptr() as *const _),
+                gl::GetUniformLocation(program, b"drawFlag\0".as_ptr() as *const _),
+            )
+        };
+
+        let (vao, vbo, ebo) = create_instance_buffers();
+        let rect_renderer = RectRenderer::new()?;
+
+        let renderer = Self {
+            program,
+            u_cell_size,
+            u_window_size,
+            u_draw_flag,
+            vao,
+            vbo,
+            ebo,
+            instances: Vec::new(),
+            cell_atlas: Vec::new(),
+            lines: 0,
+            columns: 0,
+            dpr,
+            cell_width,
+            cell_height,
+            cell_descent,
+            font_keys,
+            glyph_cache,
+            rect_renderer,
+        };
+
+        Ok(renderer)
+    }
+
+    /// Shared with [`super::vertex_fallback`], which needs identical cell metrics.
+    pub(crate) fn compute_cell_size(
+        font: &mut Font,
+        font_key: FontKey,
+    ) -> Result<(f32, f32, f32), CreationError> {
+        let metrics = font.metrics(font_key)?;
+
+        let offset_x = 0.0;
+        let offset_y = 0.0;
+
+        Ok((
+            metrics.descent,
+            ((metrics.average_advance + offset_x) as f32)
+                .floor()
+                .max(1.),
+            ((metrics.line_height + offset_y) as f32).floor().max(1.),
+        ))
+    }
+}
+
+impl Renderer for Glsl3Renderer {
+    fn draw_frame(&self) {
+        unsafe { gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT) };
+        self.rect_renderer.draw_backgrounds();
+
+        if self.instances.is_empty() {
+            self.rect_renderer.draw_decorations();
+            return;
+        }
+
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        }
+
+        let atlases: Vec<GLuint> = self.glyph_cache.atlas_textures().collect();
+
+        // One GL draw call can only sample one texture, so once glyphs have
+        // spilled into more than one atlas, each atlas needs its own instance
+        // subset uploaded and drawn with that atlas bound. The common case of
+        // a single atlas skips the per-instance filtering below entirely.
+        for &atlas_texture in &atlases {
+            let instances: Cow<[GlInstanceAttr]> = if atlases.len() == 1 {
+                Cow::Borrowed(&self.instances)
+            } else {
+                Cow::Owned(
+                    self.instances
+                        .iter()
+                        .zip(&self.cell_atlas)
+                        .filter(|(_, &tex)| tex == atlas_texture)
+                        .map(|(instance, _)| instance.clone())
+                        .collect(),
+                )
+            };
+
+            if instances.is_empty() {
+                continue;
+            }
+
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, atlas_texture);
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    0,
+                    (mem::size_of::<GlInstanceAttr>() * instances.len()) as _,
+                    instances.as_ptr() as *const _,
+                );
+            }
+
+            let count = instances.len() as GLsizei;
+            for draw_flag in 0..4 {
+                unsafe {
+                    gl::Uniform1i(self.u_draw_flag, draw_flag);
+                    match draw_flag {
+                        // draw texture(0)
+                        0 => {
+                            gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+                            gl::DrawElementsInstanced(
+                                gl::TRIANGLES,
+                                6,
+                                gl::UNSIGNED_INT,
+                                std::ptr::null(),
+                                count,
+                            );
+                        }
+                        // draw bounding box(1), cell box(2) or baseline(3)
+                        _ => {
+                            gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                            gl::DrawElementsInstanced(
+                                gl::LINE_LOOP,
+                                4,
+                                gl::UNSIGNED_INT,
+                                std::ptr::null(),
+                                count,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        self.rect_renderer.draw_decorations();
+    }
+
+    // Glyph placement is scaled by `self.cell_descent`/`self.dpr`, both kept live by
+    // `set_scale_factor`, rather than by constants baked in at some reference DPI —
+    // dragging the window to a display with a different scale factor reflows every
+    // cell already on screen the next time it's redrawn.
+    fn set_text(&mut self, row: usize, col: usize, ch: char, flags: CellFlags, fg: [f32; 3]) {
+        if row >= self.lines || col >= self.columns {
+            return;
+        }
+
+        let font_key = self
+            .font_keys
+            .select(flags.contains(CellFlags::BOLD), flags.contains(CellFlags::ITALIC));
+        let glyph = self.glyph_cache.get(GlyphKey {
+            font_key,
+            c: ch,
+            size: self.glyph_cache.font.size,
+        });
+
+        debug!(
+            "ch: {} font descent: {} glyph: {:?}",
+            ch, self.cell_descent, glyph
+        );
+
+        let (cell_descent, dpr) = (self.cell_descent, self.dpr);
+        let instance = &mut self.instances[row * self.columns + col];
+        instance.uv_width = glyph.width * dpr;
+        instance.uv_height = glyph.height * dpr;
+        instance.uv_offset_x = glyph.left * dpr;
+        instance.uv_offset_y = (glyph.top + cell_descent) * dpr;
+        instance.baseline = cell_descent * dpr;
+        instance.atlas_uv_x = glyph.atlas_uv_x;
+        instance.atlas_uv_y = glyph.atlas_uv_y;
+        instance.atlas_uv_width = glyph.atlas_uv_width;
+        instance.atlas_uv_height = glyph.atlas_uv_height;
+        instance.fg = fg;
+        self.cell_atlas[row * self.columns + col] = glyph.atlas_texture;
+    }
+
+    fn set_background(&mut self, row: usize, col: usize, color: [f32; 3]) {
+        self.rect_renderer.set_background(row, col, color);
+    }
+
+    fn set_decoration(&mut self, row: usize, col: usize, decoration: Decoration, color: [f32; 3]) {
+        self.rect_renderer.set_decoration(row, col, decoration, color);
+    }
+
+    fn resize(&self, width: u32, height: u32) {
+        unsafe {
+            gl::Viewport(0, 0, width as _, height as _);
+            gl::Uniform2f(self.u_window_size, width as f32, height as f32);
+        };
+        self.rect_renderer.resize(width, height);
+    }
+
+    fn set_size(&mut self, lines: usize, columns: usize) {
+        if lines == 0 || columns == 0 {
+            error!("Lines and columns must > 0");
+            return;
+        }
+
+        let (delta_x, delta_y) = (2.0 / (columns as f32), 2.0 / (lines as f32));
+        debug!("delta_x: {} delta_y: {}", delta_x, delta_y);
+
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::Uniform2f(self.u_cell_size, delta_x, delta_y);
+        }
+
+        let mut instances = vec![GlInstanceAttr::default(); lines * columns];
+        for y in 0..lines {
+            for x in 0..columns {
+                let instance = &mut instances[y * columns + x];
+                instance.row = y as _;
+                instance.col = x as _;
+            }
+        }
+        self.instances = instances;
+        self.cell_atlas = vec![0; lines * columns];
+        self.lines = lines;
+        self.columns = columns;
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (mem::size_of::<GlInstanceAttr>() * self.instances.len()) as _,
+                std::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::UseProgram(0);
+        }
+
+        self.rect_renderer.set_size(lines, columns);
+    }
+
+    /// Rebuild the `Rasterizer` at a new device pixel ratio and recompute cell
+    /// metrics, discarding glyphs rasterized at the old scale.
+    fn set_scale_factor(&mut self, dpr: f32) -> Result<(), CreationError> {
+        let family = self.glyph_cache.font.normal.family.clone();
+        let size = self.glyph_cache.font.size.as_f32_pts() as i32;
+
+        let mut ft = Font::new(dpr, &family, size);
+        let font_keys = ft.compute_font_keys()?;
+        let (cell_descent, cell_width, cell_height) =
+            Self::compute_cell_size(&mut ft, font_keys.regular)?;
+
+        self.dpr = dpr;
+        self.cell_width = cell_width;
+        self.cell_height = cell_height;
+        self.cell_descent = cell_descent;
+        self.font_keys = font_keys;
+
+        let mut glyph_cache = GlyphCache::new(ft)?;
+        glyph_cache.prefill_ascii(font_keys.regular);
+        self.glyph_cache = glyph_cache;
+
+        Ok(())
+    }
+
+    /// Recompile the shader program from whatever's currently on disk at
+    /// `TEXT_SHADER_*_PATH` and reload the font at the same family/size/dpr,
+    /// for [`super::LiveReloadHandle`] to call after a debounced file change.
+    fn reload(&mut self) -> Result<(), CreationError> {
+        let vertex_shader = create_shader(gl::VERTEX_SHADER, TEXT_SHADER_V_PATH, TEXT_SHADER_V)?;
+        let fragment_shader =
+            create_shader(gl::FRAGMENT_SHADER, TEXT_SHADER_F_PATH, TEXT_SHADER_F)?;
+        let program = create_program(vertex_shader, fragment_shader)?;
+
+        let family = self.glyph_cache.font.normal.family.clone();
+        let size = self.glyph_cache.font.size.as_f32_pts() as i32;
+        let mut ft = Font::new(self.dpr, &family, size);
+        let font_keys = ft.compute_font_keys()?;
+        let (cell_descent, cell_width, cell_height) =
+            Self::compute_cell_size(&mut ft, font_keys.regular)?;
+
+        let mut glyph_cache = GlyphCache::new(ft)?;
+        glyph_cache.prefill_ascii(font_keys.regular);
+
+        let (u_cell_size, u_window_size, u_draw_flag) = unsafe {
+            (
+                gl::GetUniformLocation(program, b"cellSize\0".as_ptr() as *const _),
+                gl::GetUniformLocation(program, b"windowSize\0".as_ptr() as *const _),
+                gl::GetUniformLocation(program, b"drawFlag\0".as_ptr() as *const _),
+            )
+        };
+
+        unsafe { gl::DeleteProgram(self.program) };
+        self.program = program;
+        self.u_cell_size = u_cell_size;
+        self.u_window_size = u_window_size;
+        self.u_draw_flag = u_draw_flag;
+        self.cell_width = cell_width;
+        self.cell_height = cell_height;
+        self.cell_descent = cell_descent;
+        self.font_keys = font_keys;
+        self.glyph_cache = glyph_cache;
+
+        Ok(())
+    }
+
+    fn shader_paths(&self) -> [&'static str; 2] {
+        [TEXT_SHADER_V_PATH, TEXT_SHADER_F_PATH]
+    }
+
+    fn font_family(&self) -> String {
+        self.glyph_cache.font.normal.family.clone()
+    }
+
+    fn cell_width(&self) -> f32 {
+        self.cell_width
+    }
+
+    fn cell_height(&self) -> f32 {
+        self.cell_height
+    }
+
+    fn cell_descent(&self) -> f32 {
+        self.cell_descent
+    }
+}
+
+/// GlInstanceAttr describes the instance properties passed to opengl shader.
+/// Note that the fields here are in strict order, any modifications to it MUST
+/// be synchronized with gl::VertexAttribPointer and GLSL scripts.
+#[derive(Debug, Clone, Default)]
+struct GlInstanceAttr {
+    // gridCoords
+    col: f32, // x
+    row: f32, // y
+
+    // bounding box size
+    uv_width: f32,
+    uv_height: f32,
+
+    // bounding origin point
+    uv_offset_x: f32,
+    uv_offset_y: f32,
+
+    // glyph baseline
+    baseline: f32,
+
+    // rect of this glyph within its shared atlas texture, normalized [0, 1]
+    atlas_uv_x: f32,
+    atlas_uv_y: f32,
+    atlas_uv_width: f32,
+    atlas_uv_height: f32,
+
+    // text color the glyph is tinted with
+    fg: [f32; 3],
+}
+
+/// Create the VAO/VBO/EBO shared by every cell instance. The VBO is left empty
+/// (sized by `set_size` once the grid dimensions are known); only the vertex
+/// attribute layout is fixed here.
+fn create_instance_buffers() -> (GLuint, GLuint, GLuint) {
+    let mut vao: GLuint = 0;
+    let mut vbo: GLuint = 0;
+    let mut ebo: GLuint = 0;
+    unsafe {
+        // Create VAO.
+        gl::GenVertexArrays(1, &mut vao);
+        gl::BindVertexArray(vao);
+
+        // Create EBO.
+        gl::GenBuffers(1, &mut ebo);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+
+        // Set EBO.
+        // NOTE that the vertex index vector is cleverly set here.
+        // We can either use all 6 indices to draw the texture,
+        // or we can use only the first 4 indices to draw the bounding box.
+        let indices: [u32; 6] = [0, 1, 2, 3, 0, 2];
+        gl::BufferData(
+            gl::ELEMENT_ARRAY_BUFFER,
+            (mem::size_of::<u32>() * indices.len()) as _,
+            indices.as_ptr() as _,
+            gl::STATIC_DRAW,
+        );
+
+        // Create VBO. Sized later by set_size, once the grid dimensions are known.
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+        let sizeof_attr = mem::size_of::<GlInstanceAttr>();
+        let define_vertex_attrib = |idx, n, offset| {
+            // Define vertex attrib pointer
+            gl::VertexAttribPointer(
+                idx,              // Attrib index.
+                n,                // Attrib size, in gl::FLOAT.
+                gl::FLOAT,        // Attrib type.
+                gl::FALSE,        // Don't be normalized.
+                sizeof_attr as _, // Attrib stride.
+                offset as _,      // Attrib pointer, offset of GlInstanceAttr.
+            );
+            // Enable it.
+            gl::EnableVertexAttribArray(idx);
+            // Vertex attributes are changed only when the instance changes.
+            gl::VertexAttribDivisor(idx, 1);
+            (idx + 1, offset + n * (mem::size_of::<f32>() as i32))
+        };
+
+        // Define vertex attributes.
+
+        let (idx, offset) = (0, 0);
+        // in vec2 gridCoords
+        let (idx, offset) = define_vertex_attrib(idx, 2, offset);
+        // in vec4 uvAttr
+        let (idx, offset) = define_vertex_attrib(idx, 4, offset);
+        // in float baseline
+        let (idx, offset) = define_vertex_attrib(idx, 1, offset);
+        // in vec4 atlasUvAttr
+        let (idx, offset) = define_vertex_attrib(idx, 4, offset);
+        // in vec3 fgAttr
+        let (idx, offset) = define_vertex_attrib(idx, 3, offset);
+
+        // Just for make linter happy.
+        let (_, _) = (idx, offset);
+
+        gl::BindVertexArray(0);
+    }
+
+    (vao, vbo, ebo)
+}
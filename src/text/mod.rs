@@ -0,0 +1,864 @@
+//! Text rendering sits behind two backends selected at runtime: `glsl3`, the
+//! primary instanced-rendering path for desktop-class OpenGL 3.3+, and
+//! `vertex_fallback`, a vertex-expansion fallback for desktop GL contexts
+//! lacking `glVertexAttribDivisor`/instancing support. Both share the glyph
+//! rasterization/atlas machinery defined below; only the vertex layout and
+//! draw submission differ.
+
+mod gl {
+    #![allow(clippy::all)]
+    include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
+}
+
+pub mod glsl3;
+pub mod vertex_fallback;
+mod reload;
+
+pub use reload::LiveReloadHandle;
+
+use {
+    crate::font::Font,
+    crossfont::{BitmapBuffer, GlyphKey, RasterizedGlyph},
+    gl::types::*,
+    log::info,
+    std::{collections::HashMap, default::Default, ffi::CStr, fs, io, mem, path::PathBuf, ptr},
+};
+
+bitflags::bitflags! {
+    /// Which face of the font a cell should be rendered in, analogous to
+    /// `parser::TermCell`'s own bold/italic bits.
+    #[derive(Default)]
+    pub struct CellFlags: u8 {
+        const BOLD = 0b0000_0001;
+        const ITALIC = 0b0000_0010;
+    }
+}
+
+/// Set OpenGL symbol loader. This call MUST be after window.make_current on windows.
+pub fn setup_opengl<F>(loader: F)
+where
+    F: FnMut(&'static str) -> *const GLvoid,
+{
+    gl::load_with(loader);
+}
+
+#[derive(Debug)]
+pub enum CreationError {
+    Io(io::Error),
+    Compile(PathBuf, String),
+    Link(String),
+    Font(crossfont::Error),
+    Watch(notify::Error),
+}
+
+impl From<io::Error> for CreationError {
+    fn from(val: io::Error) -> Self {
+        Self::Io(val)
+    }
+}
+
+impl From<crossfont::Error> for CreationError {
+    fn from(err: crossfont::Error) -> Self {
+        Self::Font(err)
+    }
+}
+
+impl From<notify::Error> for CreationError {
+    fn from(err: notify::Error) -> Self {
+        Self::Watch(err)
+    }
+}
+
+/// A text decoration drawn as a rect by [`RectRenderer`], rather than baked
+/// into the glyph itself. Mirrors `parser::Underline` plus strikethrough.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Decoration {
+    #[default]
+    None,
+    Underline,
+    DoubleUnderline,
+    Strikethrough,
+    Undercurl,
+    Dotted,
+    Dashed,
+}
+
+/// Backend-agnostic surface `Screen` drives the active renderer through, so it
+/// never needs to know whether `glsl3` or `vertex_fallback` is doing the drawing.
+pub trait Renderer {
+    fn set_text(&mut self, row: usize, col: usize, ch: char, flags: CellFlags, fg: [f32; 3]);
+    /// Paint a cell's background, drawn as a solid rect underneath its glyph
+    /// rather than baked into the glyph texture.
+    fn set_background(&mut self, row: usize, col: usize, color: [f32; 3]);
+    /// A text decoration (underline, strikethrough, ...), drawn as a rect on
+    /// top of the glyph rather than baked into it.
+    fn set_decoration(&mut self, row: usize, col: usize, decoration: Decoration, color: [f32; 3]);
+    fn set_size(&mut self, lines: usize, columns: usize);
+    fn resize(&self, width: u32, height: u32);
+    fn draw_frame(&self);
+    fn set_scale_factor(&mut self, dpr: f32) -> Result<(), CreationError>;
+    fn cell_width(&self) -> f32;
+    fn cell_height(&self) -> f32;
+    fn cell_descent(&self) -> f32;
+
+    /// Recompile this renderer's shader program from disk and reload its font
+    /// (picking up whatever's currently at `shader_paths`/`font_family`),
+    /// clearing the glyph cache so the next frame re-rasterizes. Called by
+    /// [`LiveReloadHandle::poll`]; grid contents are untouched, so
+    /// existing cells just redraw with the new program/glyphs on the next
+    /// `set_text` pass.
+    fn reload(&mut self) -> Result<(), CreationError>;
+    /// Vertex/fragment source paths this renderer's shader was built from, so
+    /// live reload knows what to watch.
+    fn shader_paths(&self) -> [&'static str; 2];
+    /// Font family currently in use, so live reload can also watch its file on disk.
+    fn font_family(&self) -> String;
+}
+
+/// Picks a backend for the active GL context and builds its renderer. Must be
+/// called after [`setup_opengl`].
+pub enum TextShader {
+    Glsl3(glsl3::Glsl3Renderer),
+    VertexFallback(vertex_fallback::VertexFallbackRenderer),
+}
+
+impl TextShader {
+    pub fn new(dpr: f32, font_family: &str, font_size: i32) -> Result<TextShader, CreationError> {
+        if supports_instancing() {
+            info!("GL context supports instancing; using the glsl3 renderer");
+            glsl3::Glsl3Renderer::new(dpr, font_family, font_size).map(TextShader::Glsl3)
+        } else {
+            info!("GL context lacks instancing support; falling back to the vertex-expansion renderer");
+            vertex_fallback::VertexFallbackRenderer::new(dpr, font_family, font_size)
+                .map(TextShader::VertexFallback)
+        }
+    }
+}
+
+impl Renderer for TextShader {
+    fn set_text(&mut self, row: usize, col: usize, ch: char, flags: CellFlags, fg: [f32; 3]) {
+        match self {
+            Self::Glsl3(r) => r.set_text(row, col, ch, flags, fg),
+            Self::VertexFallback(r) => r.set_text(row, col, ch, flags, fg),
+        }
+    }
+
+    fn set_background(&mut self, row: usize, col: usize, color: [f32; 3]) {
+        match self {
+            Self::Glsl3(r) => r.set_background(row, col, color),
+            Self::VertexFallback(r) => r.set_background(row, col, color),
+        }
+    }
+
+    fn set_decoration(&mut self, row: usize, col: usize, decoration: Decoration, color: [f32; 3]) {
+        match self {
+            Self::Glsl3(r) => r.set_decoration(row, col, decoration, color),
+            Self::VertexFallback(r) => r.set_decoration(row, col, decoration, color),
+        }
+    }
+
+    fn set_size(&mut self, lines: usize, columns: usize) {
+        match self {
+            Self::Glsl3(r) => r.set_size(lines, columns),
+            Self::VertexFallback(r) => r.set_size(lines, columns),
+        }
+    }
+
+    fn resize(&self, width: u32, height: u32) {
+        match self {
+            Self::Glsl3(r) => r.resize(width, height),
+            Self::VertexFallback(r) => r.resize(width, height),
+        }
+    }
+
+    fn draw_frame(&self) {
+        match self {
+            Self::Glsl3(r) => r.draw_frame(),
+            Self::VertexFallback(r) => r.draw_frame(),
+        }
+    }
+
+    fn set_scale_factor(&mut self, dpr: f32) -> Result<(), CreationError> {
+        match self {
+            Self::Glsl3(r) => r.set_scale_factor(dpr),
+            Self::VertexFallback(r) => r.set_scale_factor(dpr),
+        }
+    }
+
+    fn cell_width(&self) -> f32 {
+        match self {
+            Self::Glsl3(r) => r.cell_width(),
+            Self::VertexFallback(r) => r.cell_width(),
+        }
+    }
+
+    fn cell_height(&self) -> f32 {
+        match self {
+            Self::Glsl3(r) => r.cell_height(),
+            Self::VertexFallback(r) => r.cell_height(),
+        }
+    }
+
+    fn cell_descent(&self) -> f32 {
+        match self {
+            Self::Glsl3(r) => r.cell_descent(),
+            Self::VertexFallback(r) => r.cell_descent(),
+        }
+    }
+
+    fn reload(&mut self) -> Result<(), CreationError> {
+        match self {
+            Self::Glsl3(r) => r.reload(),
+            Self::VertexFallback(r) => r.reload(),
+        }
+    }
+
+    fn shader_paths(&self) -> [&'static str; 2] {
+        match self {
+            Self::Glsl3(r) => r.shader_paths(),
+            Self::VertexFallback(r) => r.shader_paths(),
+        }
+    }
+
+    fn font_family(&self) -> String {
+        match self {
+            Self::Glsl3(r) => r.font_family(),
+            Self::VertexFallback(r) => r.font_family(),
+        }
+    }
+}
+
+/// Does the active GL context support instanced draws (core
+/// `glDrawElementsInstanced`/`glVertexAttribDivisor` on GL 3.3+, or the
+/// `ANGLE_instanced_arrays`/`ARB_instanced_arrays` extensions on older
+/// contexts)? If not, [`TextShader::new`] falls back to the `vertex_fallback`
+/// backend.
+fn supports_instancing() -> bool {
+    let version = unsafe { CStr::from_ptr(gl::GetString(gl::VERSION) as *const _) }
+        .to_string_lossy()
+        .into_owned();
+
+    if let Some((major, minor)) = parse_gl_version(&version) {
+        if (major, minor) >= (3, 3) {
+            return true;
+        }
+    }
+
+    extension_supported("GL_ARB_instanced_arrays") || extension_supported("GL_ANGLE_instanced_arrays")
+}
+
+/// Pulls the leading `major.minor` out of a `GL_VERSION` string, which may be
+/// prefixed (e.g. `"OpenGL ES 2.0 ..."`) or suffixed with vendor info.
+fn parse_gl_version(version: &str) -> Option<(u32, u32)> {
+    version.split(|c: char| !c.is_ascii_digit() && c != '.').find_map(|token| {
+        let mut parts = token.splitn(3, '.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        Some((major, minor))
+    })
+}
+
+fn extension_supported(name: &str) -> bool {
+    let mut count = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count) };
+    (0..count).any(|i| {
+        let ext = unsafe { CStr::from_ptr(gl::GetStringi(gl::EXTENSIONS, i as _) as *const _) };
+        ext.to_string_lossy() == name
+    })
+}
+
+#[derive(Copy, Debug, Clone, Default)]
+pub struct Glyph {
+    /// The GL texture object of the atlas this glyph was packed into.
+    pub atlas_texture: GLuint,
+    /// This glyph's rect within `atlas_texture`, normalized to [0, 1].
+    pub atlas_uv_x: f32,
+    pub atlas_uv_y: f32,
+    pub atlas_uv_width: f32,
+    pub atlas_uv_height: f32,
+
+    pub top: f32,
+    pub left: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Side length, in pixels, of each backing texture an `Atlas` allocates.
+const ATLAS_SIZE: i32 = 1024;
+
+/// A single fixed-size (`ATLAS_SIZE` square) texture that glyphs are packed into using shelf/row packing:
+/// glyphs are placed left-to-right along a "row" until one doesn't fit, then a new
+/// row starts below it; the atlas is full once a row doesn't fit height-wise either.
+struct Atlas {
+    texture: GLuint,
+    row_x: i32,
+    row_y: i32,
+    row_height: i32,
+}
+
+impl Atlas {
+    fn new() -> Self {
+        let mut texture: GLuint = 0;
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as _,
+                ATLAS_SIZE,
+                ATLAS_SIZE,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                ptr::null(),
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Self {
+            texture,
+            row_x: 0,
+            row_y: 0,
+            row_height: 0,
+        }
+    }
+
+    /// Shelf-pack a `width`x`height` rect into this atlas, returning the
+    /// texel coordinates it was placed at. `None` means the atlas has no room
+    /// left; the caller should allocate another. Split out from `insert` so the
+    /// packing math can be unit tested without a live GL context.
+    fn place(&mut self, width: i32, height: i32) -> Option<(i32, i32)> {
+        if self.row_x + width > ATLAS_SIZE {
+            self.row_y += self.row_height;
+            self.row_x = 0;
+            self.row_height = 0;
+        }
+
+        if self.row_y + height > ATLAS_SIZE {
+            return None;
+        }
+
+        let placed = (self.row_x, self.row_y);
+        self.row_x += width;
+        self.row_height = self.row_height.max(height);
+        Some(placed)
+    }
+
+    /// Try to pack `glyph` into this atlas, returning its normalized UV rect.
+    /// `None` means the atlas has no room left; the caller should allocate another.
+    fn insert(&mut self, glyph: &RasterizedGlyph) -> Option<Glyph> {
+        let (width, height) = (glyph.width, glyph.height);
+        let (x, y) = self.place(width, height)?;
+
+        let (format, buf) = match &glyph.buf {
+            BitmapBuffer::RGB(buf) => (gl::RGB, buf.as_slice()),
+            BitmapBuffer::RGBA(buf) => (gl::RGBA, buf.as_slice()),
+        };
+
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x,
+                y,
+                width,
+                height,
+                format,
+                gl::UNSIGNED_BYTE,
+                buf.as_ptr() as *const _,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        let atlas_size = ATLAS_SIZE as f32;
+        Some(Glyph {
+            atlas_texture: self.texture,
+            atlas_uv_x: x as f32 / atlas_size,
+            atlas_uv_y: y as f32 / atlas_size,
+            atlas_uv_width: width as f32 / atlas_size,
+            atlas_uv_height: height as f32 / atlas_size,
+            top: glyph.top as _,
+            left: glyph.left as _,
+            width: width as _,
+            height: height as _,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atlas() -> Atlas {
+        Atlas {
+            texture: 0,
+            row_x: 0,
+            row_y: 0,
+            row_height: 0,
+        }
+    }
+
+    #[test]
+    fn packs_glyphs_left_to_right_on_one_row() {
+        let mut atlas = atlas();
+        assert_eq!(atlas.place(10, 20), Some((0, 0)));
+        assert_eq!(atlas.place(10, 30), Some((10, 0)));
+        assert_eq!(atlas.row_height, 30);
+    }
+
+    #[test]
+    fn starts_a_new_row_once_width_overflows() {
+        let mut atlas = atlas();
+        atlas.place(ATLAS_SIZE - 5, 20).unwrap();
+        assert_eq!(atlas.place(10, 15), Some((0, 20)));
+    }
+
+    #[test]
+    fn returns_none_once_the_atlas_is_full() {
+        let mut atlas = atlas();
+        atlas.row_y = ATLAS_SIZE - 5;
+        assert_eq!(atlas.place(10, 20), None);
+    }
+}
+
+impl Drop for Atlas {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.texture) };
+    }
+}
+
+pub struct GlyphCache {
+    /// Cache of buffered glyphs.
+    cache: HashMap<GlyphKey, Glyph>,
+
+    /// Backing atlas textures, in allocation order; new glyphs go into the last one
+    /// until it's full, at which point another atlas is allocated.
+    atlases: Vec<Atlas>,
+
+    pub(crate) font: Font,
+}
+
+impl GlyphCache {
+    pub fn new(font: Font) -> Result<GlyphCache, crossfont::Error> {
+        let cache = Self {
+            cache: HashMap::default(),
+            atlases: Vec::new(),
+            font,
+        };
+
+        Ok(cache)
+    }
+
+    /// Eagerly rasterize the printable ASCII range so the first frame of typical
+    /// shell output doesn't pay for a cache miss per glyph.
+    pub fn prefill_ascii(&mut self, font_key: crossfont::FontKey) {
+        for c in (0x20u8..=0x7e).map(char::from) {
+            self.get(GlyphKey {
+                font_key,
+                c,
+                size: self.font.size,
+            });
+        }
+    }
+
+    pub fn get(&mut self, glyph_key: GlyphKey) -> Glyph {
+        if let Some(glyph) = self.cache.get(&glyph_key) {
+            return *glyph;
+        }
+
+        let rasterized = self
+            .font
+            .get_glyph(glyph_key)
+            .unwrap_or_else(|_| Default::default());
+
+        let glyph = self.load_glyph(&rasterized);
+        self.cache.insert(glyph_key, glyph);
+
+        glyph
+    }
+
+    pub fn load_glyph(&mut self, glyph: &RasterizedGlyph) -> Glyph {
+        if let Some(atlas) = self.atlases.last_mut() {
+            if let Some(result) = atlas.insert(glyph) {
+                return result;
+            }
+        }
+
+        let mut atlas = Atlas::new();
+        let result = atlas
+            .insert(glyph)
+            .expect("a fresh atlas must fit any single glyph");
+        self.atlases.push(atlas);
+
+        result
+    }
+
+    /// Drop every atlas texture and cached glyph, e.g. after a DPI or font-size
+    /// change makes all previously-rasterized glyphs the wrong size.
+    pub fn clear(&mut self) {
+        self.atlases.clear();
+        self.cache.clear();
+    }
+
+    /// Every atlas texture currently allocated, in allocation order. Usually
+    /// just one, but a session that rasterizes enough distinct glyphs (large
+    /// font sizes, CJK-heavy output) overflows a single `ATLAS_SIZE` texture
+    /// and spills into more; callers must bind and draw each one separately; a
+    /// single draw call can only sample one texture at a time.
+    pub fn atlas_textures(&self) -> impl Iterator<Item = GLuint> + '_ {
+        self.atlases.iter().map(|atlas| atlas.texture)
+    }
+}
+
+impl Decoration {
+    /// Pattern id consumed by the rect fragment shader to decide what to paint
+    /// within a cell's quad; must stay in sync with `res/rect.f.glsl`. `0`
+    /// means nothing is drawn (the shader discards the fragment) and is kept
+    /// distinct from [`RectRenderer::FILL_KIND`], used for backgrounds.
+    fn shader_kind(self) -> f32 {
+        match self {
+            Decoration::None => 0.0,
+            Decoration::Underline => 1.0,
+            Decoration::DoubleUnderline => 2.0,
+            Decoration::Strikethrough => 3.0,
+            Decoration::Undercurl => 4.0,
+            Decoration::Dotted => 5.0,
+            Decoration::Dashed => 6.0,
+        }
+    }
+}
+
+static RECT_SHADER_V_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/rect.v.glsl");
+static RECT_SHADER_F_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/rect.f.glsl");
+
+static RECT_SHADER_V: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/rect.v.glsl"));
+static RECT_SHADER_F: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/rect.f.glsl"));
+
+/// One corner of a cell's quad as drawn by [`RectRenderer`]. Unlike the glyph
+/// instance buffers, rects are expanded into real vertices (4 per quad) rather
+/// than instanced, since there are at most two rects per cell (background and
+/// decoration) and this is shared as-is by both the `glsl3` and `vertex_fallback`
+/// backends rather than needing its own per-backend variant.
+#[derive(Debug, Clone, Copy, Default)]
+struct RectVertex {
+    corner: f32,
+    col: f32,
+    row: f32,
+    color: [f32; 3],
+    kind: f32,
+}
+
+/// Solid-color rect subsystem shared by both renderer backends: cell
+/// backgrounds and text decorations (underline, strikethrough, undercurl, ...)
+/// are drawn as rects here rather than baked into the glyph atlas, so a
+/// `CSI 4:3 m` (curly underline) doesn't need its own rasterized glyph.
+pub(crate) struct RectRenderer {
+    program: GLuint,
+    u_cell_size: GLint,
+    u_window_size: GLint,
+    vao: GLuint,
+    vbo: GLuint,
+    ebo: GLuint,
+
+    /// Cell backgrounds, one quad per cell, indexed by `row * columns + col`.
+    backgrounds: Vec<RectVertex>,
+    /// Text decorations drawn on top of the glyphs, same indexing as `backgrounds`.
+    decorations: Vec<RectVertex>,
+    lines: usize,
+    columns: usize,
+}
+
+impl RectRenderer {
+    /// `kind` value meaning "fill the whole cell", used for backgrounds; kept
+    /// out of [`Decoration::shader_kind`]'s range (which starts at `0`) so the
+    /// fragment shader can tell the two passes apart if they're ever merged.
+    const FILL_KIND: f32 = -1.0;
+
+    pub(crate) fn new() -> Result<Self, CreationError> {
+        let vertex_shader = create_shader(gl::VERTEX_SHADER, RECT_SHADER_V_PATH, RECT_SHADER_V)?;
+        let fragment_shader =
+            create_shader(gl::FRAGMENT_SHADER, RECT_SHADER_F_PATH, RECT_SHADER_F)?;
+        let program = create_program(vertex_shader, fragment_shader)?;
+
+        let (u_cell_size, u_window_size) = unsafe {
+            (
+                gl::GetUniformLocation(program, b"cellSize\0".as_ptr() as *const _),
+                gl::GetUniformLocation(program, b"windowSize\0".as_ptr() as *const _),
+            )
+        };
+
+        let (vao, vbo, ebo) = create_rect_buffers();
+
+        Ok(Self {
+            program,
+            u_cell_size,
+            u_window_size,
+            vao,
+            vbo,
+            ebo,
+            backgrounds: Vec::new(),
+            decorations: Vec::new(),
+            lines: 0,
+            columns: 0,
+        })
+    }
+
+    pub(crate) fn resize(&self, width: u32, height: u32) {
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::Uniform2f(self.u_window_size, width as f32, height as f32);
+            gl::UseProgram(0);
+        }
+    }
+
+    pub(crate) fn set_size(&mut self, lines: usize, columns: usize) {
+        let (delta_x, delta_y) = (2.0 / (columns as f32), 2.0 / (lines as f32));
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::Uniform2f(self.u_cell_size, delta_x, delta_y);
+            gl::UseProgram(0);
+        }
+
+        let cell_count = lines * columns;
+        let mut layer = vec![RectVertex::default(); cell_count * 4];
+        let mut indices = Vec::with_capacity(cell_count * 6);
+        for y in 0..lines {
+            for x in 0..columns {
+                let cell = y * columns + x;
+                for corner in 0..4u32 {
+                    let vertex = &mut layer[cell * 4 + corner as usize];
+                    vertex.corner = corner as f32;
+                    vertex.row = y as _;
+                    vertex.col = x as _;
+                }
+                for i in [0u32, 1, 2, 3, 0, 2] {
+                    indices.push((cell as u32) * 4 + i);
+                }
+            }
+        }
+        self.backgrounds = layer.clone();
+        self.decorations = layer;
+        self.lines = lines;
+        self.columns = columns;
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (mem::size_of::<RectVertex>() * self.backgrounds.len()) as _,
+                ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (mem::size_of::<u32>() * indices.len()) as _,
+                indices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+        }
+    }
+
+    pub(crate) fn set_background(&mut self, row: usize, col: usize, color: [f32; 3]) {
+        self.paint(true, row, col, Self::FILL_KIND, color);
+    }
+
+    pub(crate) fn set_decoration(&mut self, row: usize, col: usize, decoration: Decoration, color: [f32; 3]) {
+        self.paint(false, row, col, decoration.shader_kind(), color);
+    }
+
+    fn paint(&mut self, background: bool, row: usize, col: usize, kind: f32, color: [f32; 3]) {
+        if row >= self.lines || col >= self.columns {
+            return;
+        }
+
+        let layer = if background { &mut self.backgrounds } else { &mut self.decorations };
+        let base = (row * self.columns + col) * 4;
+        for corner in 0..4usize {
+            let vertex = &mut layer[base + corner];
+            vertex.kind = kind;
+            vertex.color = color;
+        }
+    }
+
+    /// Draw cell backgrounds. Called before glyphs so text composites on top.
+    pub(crate) fn draw_backgrounds(&self) {
+        self.draw(&self.backgrounds);
+    }
+
+    /// Draw text decorations (underline, strikethrough, ...). Called after
+    /// glyphs so decorations composite on top of the text they annotate.
+    pub(crate) fn draw_decorations(&self) {
+        self.draw(&self.decorations);
+    }
+
+    fn draw(&self, layer: &[RectVertex]) {
+        if layer.is_empty() {
+            return;
+        }
+
+        let cell_count = layer.len() / 4;
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (mem::size_of::<RectVertex>() * layer.len()) as _,
+                layer.as_ptr() as *const _,
+            );
+            gl::DrawElements(
+                gl::TRIANGLES,
+                (cell_count * 6) as GLsizei,
+                gl::UNSIGNED_INT,
+                ptr::null(),
+            );
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+/// Create the VAO/VBO/EBO shared by every background/decoration rect. Both
+/// buffers are left empty; `set_size` sizes the VBO and uploads the
+/// grid-sized index pattern once the grid dimensions are known. Only the
+/// vertex attribute layout is fixed here.
+fn create_rect_buffers() -> (GLuint, GLuint, GLuint) {
+    let mut vao: GLuint = 0;
+    let mut vbo: GLuint = 0;
+    let mut ebo: GLuint = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+        gl::BindVertexArray(vao);
+
+        gl::GenBuffers(1, &mut ebo);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+        let sizeof_attr = mem::size_of::<RectVertex>();
+        let define_vertex_attrib = |idx, n, offset| {
+            gl::VertexAttribPointer(
+                idx,
+                n,
+                gl::FLOAT,
+                gl::FALSE,
+                sizeof_attr as _,
+                offset as _,
+            );
+            gl::EnableVertexAttribArray(idx);
+            (idx + 1, offset + n * (mem::size_of::<f32>() as i32))
+        };
+
+        let (idx, offset) = (0, 0);
+        // in float cornerAttr
+        let (idx, offset) = define_vertex_attrib(idx, 1, offset);
+        // in vec2 gridCoords
+        let (idx, offset) = define_vertex_attrib(idx, 2, offset);
+        // in vec3 colorAttr
+        let (idx, offset) = define_vertex_attrib(idx, 3, offset);
+        // in float kindAttr
+        let (idx, offset) = define_vertex_attrib(idx, 1, offset);
+
+        let (_, _) = (idx, offset);
+
+        gl::BindVertexArray(0);
+    }
+
+    (vao, vbo, ebo)
+}
+
+pub(crate) fn create_shader(kind: GLenum, path: &str, source: &str) -> Result<GLuint, CreationError> {
+    let source = if let Ok(string) = fs::read_to_string(path) {
+        string
+    } else {
+        String::from(source)
+    };
+    let len: [GLint; 1] = [source.len() as _];
+
+    unsafe {
+        let shader = gl::CreateShader(kind);
+        gl::ShaderSource(shader, 1, &(source.as_ptr() as *const _), len.as_ptr());
+        gl::CompileShader(shader);
+
+        let mut success: GLint = 0;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+
+        if success == GLint::from(gl::TRUE) {
+            Ok(shader)
+        } else {
+            let log = gl_get_info_log(gl::SHADER, shader);
+            gl::DeleteShader(shader);
+            Err(CreationError::Compile(PathBuf::from(path), log))
+        }
+    }
+}
+
+pub(crate) fn create_program(vertex: GLuint, fragment: GLuint) -> Result<GLuint, CreationError> {
+    unsafe {
+        let program = gl::CreateProgram();
+        gl::AttachShader(program, vertex);
+        gl::AttachShader(program, fragment);
+        gl::LinkProgram(program);
+        gl::DetachShader(program, vertex);
+        gl::DetachShader(program, fragment);
+        gl::DeleteShader(vertex);
+        gl::DeleteShader(fragment);
+
+        let mut success: GLint = 0;
+        gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+
+        if success == GLint::from(gl::TRUE) {
+            gl::UseProgram(program);
+            Ok(program)
+        } else {
+            let log = gl_get_info_log(gl::PROGRAM, program);
+            gl::DeleteProgram(program);
+            Err(CreationError::Link(log))
+        }
+    }
+}
+
+fn gl_get_info_log(kind: GLenum, obj: GLuint) -> String {
+    let mut max_length: GLint = 0;
+
+    let len_func = match kind {
+        gl::PROGRAM => gl::GetProgramiv,
+        gl::SHADER => gl::GetShaderiv,
+        _ => return String::new(),
+    };
+
+    let log_func = match kind {
+        gl::PROGRAM => gl::GetProgramInfoLog,
+        gl::SHADER => gl::GetShaderInfoLog,
+        _ => return String::new(),
+    };
+
+    unsafe {
+        len_func(obj, gl::INFO_LOG_LENGTH, &mut max_length);
+    }
+
+    let mut actual_length: GLint = 0;
+    let mut buf: Vec<u8> = Vec::with_capacity(max_length as _);
+
+    unsafe {
+        log_func(
+            obj,
+            max_length,
+            &mut actual_length,
+            buf.as_mut_ptr() as *mut _,
+        );
+        buf.set_len(actual_length as _);
+    }
+
+    String::from_utf8(buf).unwrap()
+}
@@ -0,0 +1,106 @@
+//! Opt-in hot reload for [`super::TextShader`]: a background thread watches the
+//! active shader source files and font file (`create_shader` already prefers
+//! reading shader source from disk over the embedded string, so editing one of
+//! those paths is enough to trigger this), debounces the resulting burst of
+//! filesystem events, and hands back a single "something changed" signal that
+//! the render thread polls once per frame. Recompiling the GL program and
+//! reloading the font both require the GL context current on the render
+//! thread, so the watcher thread only detects changes; [`LiveReloadHandle::poll`]
+//! does the actual reload.
+
+use {
+    super::{CreationError, Renderer, TextShader},
+    crate::font,
+    log::{info, warn},
+    notify::{RecommendedWatcher, RecursiveMode, Watcher},
+    std::{
+        path::PathBuf,
+        sync::mpsc::{self, Receiver, Sender, TryRecvError},
+        thread,
+        time::Duration,
+    },
+};
+
+/// How long to wait after the last filesystem event before treating a burst of
+/// writes (editors often touch a file more than once per save) as one reload.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Handle returned by [`TextShader::enable_live_reload`]. Dropping it stops the
+/// watcher thread; nothing else needs to be done to tear it down.
+pub struct LiveReloadHandle {
+    _watcher: RecommendedWatcher,
+    changed: Receiver<()>,
+}
+
+impl LiveReloadHandle {
+    /// Non-blocking: if a debounced filesystem change arrived since the last
+    /// call, recompile `shader`'s program and reload its font/glyph cache in
+    /// place and return `Ok(true)`. Grid contents are untouched, but the new
+    /// program starts with its `cellSize`/`windowSize` uniforms unset and the
+    /// cell metrics may have changed, so the caller re-applies the window and
+    /// grid size (`resize`/`set_size`) before redrawing.
+    pub fn poll(&self, shader: &mut TextShader) -> Result<bool, CreationError> {
+        match self.changed.try_recv() {
+            Ok(()) => {
+                // A single reload already covers every signal that piled up
+                // behind this one, so drain them without acting again.
+                while self.changed.try_recv().is_ok() {}
+                shader.reload()?;
+                info!("live-reloaded shader/font");
+                Ok(true)
+            }
+            Err(TryRecvError::Empty) => Ok(false),
+            Err(TryRecvError::Disconnected) => Ok(false),
+        }
+    }
+}
+
+impl TextShader {
+    /// Watch this shader's GLSL source files and active font file, so editing
+    /// either on disk hot-swaps this `TextShader`'s program/font the next time
+    /// [`LiveReloadHandle::poll`] is called. Opt-in since it spawns a
+    /// background thread; only worth enabling during development.
+    pub fn enable_live_reload(&self) -> Result<LiveReloadHandle, CreationError> {
+        let mut paths: Vec<PathBuf> = self.shader_paths().iter().map(PathBuf::from).collect();
+        if let Some(font_path) = font::resolve_font_path(&self.font_family()) {
+            paths.push(font_path);
+        }
+
+        let (raw_tx, raw_rx) = mpsc::channel();
+        let mut watcher: RecommendedWatcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+                Ok(event) => drop(raw_tx.send(event)),
+                Err(err) => warn!("live reload: watch error: {}", err),
+            })?;
+
+        for path in &paths {
+            if let Err(err) = watcher.watch(path, RecursiveMode::NonRecursive) {
+                warn!("live reload: not watching {}: {}", path.display(), err);
+            }
+        }
+
+        let (changed_tx, changed_rx) = mpsc::channel();
+        thread::spawn(move || debounce_loop(raw_rx, changed_tx));
+
+        Ok(LiveReloadHandle {
+            _watcher: watcher,
+            changed: changed_rx,
+        })
+    }
+}
+
+/// Coalesce a burst of raw filesystem events into a single "something
+/// changed" signal, fired `DEBOUNCE` after the last event in the burst.
+fn debounce_loop(raw_rx: Receiver<notify::Event>, changed_tx: Sender<()>) {
+    loop {
+        // Block for the first event of a new burst.
+        if raw_rx.recv().is_err() {
+            return;
+        }
+        // Keep absorbing events until the burst goes quiet for `DEBOUNCE`.
+        while raw_rx.recv_timeout(DEBOUNCE).is_ok() {}
+        if changed_tx.send(()).is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,520 @@
+//! Fallback renderer for GL contexts without instanced-draw support (see
+//! [`super::supports_instancing`]). Where [`super::glsl3`] uploads one instance
+//! per cell and lets the GPU replicate its quad via `glVertexAttribDivisor`,
+//! here every cell's 4 corners are expanded into the vertex buffer up front,
+//! so a single `glDrawElements` call still covers the whole grid without
+//! relying on instancing. Slower to update (4x the vertex data to re-upload
+//! per changed cell), but needs nothing beyond the same desktop-GL core
+//! profile `glsl3` already requires.
+//!
+//! Despite the module name this targets desktop GL drivers that merely lack
+//! `glVertexAttribDivisor`/`ANGLE_instanced_arrays`, not real OpenGL ES 2.0:
+//! `build.rs` only ever generates desktop `Api::Gl` bindings, and this file
+//! calls `gl::GenVertexArrays`/`gl::BindVertexArray`, neither of which exist
+//! in core GLES2. Running on actual GLES2-only hardware (Raspberry Pi,
+//! embedded) would need a real ES context request in `Screen::new` plus ES
+//! bindings from `build.rs`, which this module does not provide.
+
+use {
+    super::{
+        create_program, create_shader, CellFlags, CreationError, Decoration, GlyphCache,
+        RectRenderer, Renderer,
+    },
+    crate::font::{Font, FontKeys},
+    crossfont::GlyphKey,
+    gl::types::*,
+    log::{debug, error},
+    std::mem,
+};
+
+use super::gl;
+
+static TEXT_SHADER_V_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.v.fallback.glsl");
+static TEXT_SHADER_F_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.f.fallback.glsl");
+
+static TEXT_SHADER_V: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.v.fallback.glsl"));
+static TEXT_SHADER_F: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.f.fallback.glsl"));
+
+/// Indices of one cell's two triangles over its 4 corner vertices, matching
+/// `corner` 0..3 in the vertex shader: bottom-left, bottom-right, top-right, top-left.
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 3, 0, 2];
+
+pub struct VertexFallbackRenderer {
+    program: GLuint,
+    u_cell_size: GLint,
+    u_window_size: GLint,
+
+    vao: GLuint,
+    vbo: GLuint,
+    ebo: GLuint,
+    /// CPU-side mirror of the VBO: 4 vertices per cell, indexed by
+    /// `(row * columns + col) * 4 + corner`.
+    vertices: Vec<GlVertexAttr>,
+    /// Atlas texture each cell's glyph was packed into, indexed by
+    /// `row * columns + col`; used to group cells by atlas at draw time.
+    cell_atlas: Vec<GLuint>,
+    lines: usize,
+    columns: usize,
+
+    dpr: f32,
+    cell_width: f32,
+    cell_height: f32,
+    cell_descent: f32,
+
+    glyph_cache: GlyphCache,
+    font_keys: FontKeys,
+
+    /// Draws cell backgrounds and text decorations (underline, strikethrough,
+    /// ...) as solid-color rects, around the batched glyph draw below.
+    rect_renderer: RectRenderer,
+}
+
+impl VertexFallbackRenderer {
+    pub fn new(dpr: f32, font_family: &str, font_size: i32) -> Result<VertexFallbackRenderer, CreationError> {
+        let vertex_shader = create_shader(gl::VERTEX_SHADER, TEXT_SHADER_V_PATH, TEXT_SHADER_V)?;
+        let fragment_shader =
+            create_shader(gl::FRAGMENT_SHADER, TEXT_SHADER_F_PATH, TEXT_SHADER_F)?;
+        let program = create_program(vertex_shader, fragment_shader)?;
+
+        let mut ft = Font::new(dpr, font_family, font_size);
+        let font_keys = ft.compute_font_keys()?;
+
+        let (cell_descent, cell_width, cell_height) =
+            super::glsl3::Glsl3Renderer::compute_cell_size(&mut ft, font_keys.regular)?;
+
+        let mut glyph_cache = GlyphCache::new(ft)?;
+        glyph_cache.prefill_ascii(font_keys.regular);
+
+        let (u_cell_size, u_window_size) = unsafe {
+            (
+                gl::GetUniformLocation(program, b"cellSize\0".as_ptr() as *const _),
+                gl::GetUniformLocation(program, b"windowSize\0".as_ptr() as *const _),
+            )
+        };
+
+        let (vao, vbo, ebo) = create_vertex_buffers();
+        let rect_renderer = RectRenderer::new()?;
+
+        Ok(Self {
+            program,
+            u_cell_size,
+            u_window_size,
+            vao,
+            vbo,
+            ebo,
+            vertices: Vec::new(),
+            cell_atlas: Vec::new(),
+            lines: 0,
+            columns: 0,
+            dpr,
+            cell_width,
+            cell_height,
+            cell_descent,
+            font_keys,
+            glyph_cache,
+            rect_renderer,
+        })
+    }
+}
+
+impl Renderer for VertexFallbackRenderer {
+    fn draw_frame(&self) {
+        unsafe { gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT) };
+        self.rect_renderer.draw_backgrounds();
+
+        if self.vertices.is_empty() {
+            self.rect_renderer.draw_decorations();
+            return;
+        }
+
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        }
+
+        let atlases: Vec<GLuint> = self.glyph_cache.atlas_textures().collect();
+
+        // One GL draw call can only sample one texture, so once glyphs have
+        // spilled into more than one atlas, each atlas needs its own vertex
+        // subset (and a matching local index buffer) uploaded and drawn with
+        // that atlas bound. The common case of a single atlas re-uses the
+        // existing full vertex/index buffers untouched.
+        for &atlas_texture in &atlases {
+            let cell_count = if atlases.len() == 1 {
+                self.vertices.len() / 4
+            } else {
+                self.cell_atlas.iter().filter(|&&tex| tex == atlas_texture).count()
+            };
+
+            if cell_count == 0 {
+                continue;
+            }
+
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, atlas_texture);
+            }
+
+            if atlases.len() == 1 {
+                unsafe {
+                    gl::BufferSubData(
+                        gl::ARRAY_BUFFER,
+                        0,
+                        (mem::size_of::<GlVertexAttr>() * self.vertices.len()) as _,
+                        self.vertices.as_ptr() as *const _,
+                    );
+                    gl::DrawElements(
+                        gl::TRIANGLES,
+                        (cell_count * QUAD_INDICES.len()) as GLsizei,
+                        gl::UNSIGNED_INT,
+                        std::ptr::null(),
+                    );
+                }
+                continue;
+            }
+
+            let mut vertices = Vec::with_capacity(cell_count * 4);
+            let mut indices = Vec::with_capacity(cell_count * QUAD_INDICES.len());
+            for (cell, &tex) in self.cell_atlas.iter().enumerate() {
+                if tex != atlas_texture {
+                    continue;
+                }
+                let local_cell = (vertices.len() / 4) as u32;
+                vertices.extend_from_slice(&self.vertices[cell * 4..cell * 4 + 4]);
+                indices.extend(QUAD_INDICES.iter().map(|i| local_cell * 4 + i));
+            }
+
+            unsafe {
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    0,
+                    (mem::size_of::<GlVertexAttr>() * vertices.len()) as _,
+                    vertices.as_ptr() as *const _,
+                );
+                gl::BufferSubData(
+                    gl::ELEMENT_ARRAY_BUFFER,
+                    0,
+                    (mem::size_of::<u32>() * indices.len()) as _,
+                    indices.as_ptr() as *const _,
+                );
+                gl::DrawElements(
+                    gl::TRIANGLES,
+                    indices.len() as GLsizei,
+                    gl::UNSIGNED_INT,
+                    std::ptr::null(),
+                );
+            }
+        }
+
+        // The static full-grid index buffer was overwritten above only in the
+        // multi-atlas branch; restore it so the next single-atlas frame (or
+        // the common case that never took that branch) still draws correctly.
+        if atlases.len() > 1 {
+            let cell_count = self.vertices.len() / 4;
+            let indices: Vec<u32> = (0..cell_count as u32)
+                .flat_map(|cell| QUAD_INDICES.iter().map(move |i| cell * 4 + i))
+                .collect();
+            unsafe {
+                gl::BufferSubData(
+                    gl::ELEMENT_ARRAY_BUFFER,
+                    0,
+                    (mem::size_of::<u32>() * indices.len()) as _,
+                    indices.as_ptr() as *const _,
+                );
+            }
+        }
+
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        self.rect_renderer.draw_decorations();
+    }
+
+    // Glyph placement is scaled by `self.cell_descent`/`self.dpr`, both kept live by
+    // `set_scale_factor`, rather than by constants baked in at some reference DPI —
+    // dragging the window to a display with a different scale factor reflows every
+    // cell already on screen the next time it's redrawn.
+    fn set_text(&mut self, row: usize, col: usize, ch: char, flags: CellFlags, fg: [f32; 3]) {
+        if row >= self.lines || col >= self.columns {
+            return;
+        }
+
+        let font_key = self
+            .font_keys
+            .select(flags.contains(CellFlags::BOLD), flags.contains(CellFlags::ITALIC));
+        let glyph = self.glyph_cache.get(GlyphKey {
+            font_key,
+            c: ch,
+            size: self.glyph_cache.font.size,
+        });
+
+        debug!(
+            "ch: {} font descent: {} glyph: {:?}",
+            ch, self.cell_descent, glyph
+        );
+
+        let (cell_descent, dpr) = (self.cell_descent, self.dpr);
+        let base = (row * self.columns + col) * 4;
+        for corner in 0..4u32 {
+            let vertex = &mut self.vertices[base + corner as usize];
+            vertex.uv_width = glyph.width * dpr;
+            vertex.uv_height = glyph.height * dpr;
+            vertex.uv_offset_x = glyph.left * dpr;
+            vertex.uv_offset_y = (glyph.top + cell_descent) * dpr;
+            vertex.baseline = cell_descent * dpr;
+            vertex.atlas_uv_x = glyph.atlas_uv_x;
+            vertex.atlas_uv_y = glyph.atlas_uv_y;
+            vertex.atlas_uv_width = glyph.atlas_uv_width;
+            vertex.atlas_uv_height = glyph.atlas_uv_height;
+            vertex.fg = fg;
+        }
+        self.cell_atlas[row * self.columns + col] = glyph.atlas_texture;
+    }
+
+    fn set_background(&mut self, row: usize, col: usize, color: [f32; 3]) {
+        self.rect_renderer.set_background(row, col, color);
+    }
+
+    fn set_decoration(&mut self, row: usize, col: usize, decoration: Decoration, color: [f32; 3]) {
+        self.rect_renderer.set_decoration(row, col, decoration, color);
+    }
+
+    fn resize(&self, width: u32, height: u32) {
+        unsafe {
+            gl::Viewport(0, 0, width as _, height as _);
+            gl::UseProgram(self.program);
+            gl::Uniform2f(self.u_window_size, width as f32, height as f32);
+        };
+        self.rect_renderer.resize(width, height);
+    }
+
+    fn set_size(&mut self, lines: usize, columns: usize) {
+        if lines == 0 || columns == 0 {
+            error!("Lines and columns must > 0");
+            return;
+        }
+
+        let (delta_x, delta_y) = (2.0 / (columns as f32), 2.0 / (lines as f32));
+
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::Uniform2f(self.u_cell_size, delta_x, delta_y);
+        }
+
+        let cell_count = lines * columns;
+        let mut vertices = vec![GlVertexAttr::default(); cell_count * 4];
+        let mut indices = Vec::with_capacity(cell_count * QUAD_INDICES.len());
+        for y in 0..lines {
+            for x in 0..columns {
+                let cell = y * columns + x;
+                for corner in 0..4u32 {
+                    let vertex = &mut vertices[cell * 4 + corner as usize];
+                    vertex.corner = corner as f32;
+                    vertex.row = y as _;
+                    vertex.col = x as _;
+                }
+                indices.extend(QUAD_INDICES.iter().map(|i| (cell as u32) * 4 + i));
+            }
+        }
+        self.vertices = vertices;
+        self.cell_atlas = vec![0; lines * columns];
+        self.lines = lines;
+        self.columns = columns;
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (mem::size_of::<GlVertexAttr>() * self.vertices.len()) as _,
+                std::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (mem::size_of::<u32>() * indices.len()) as _,
+                indices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+            gl::UseProgram(0);
+        }
+
+        self.rect_renderer.set_size(lines, columns);
+    }
+
+    /// Rebuild the `Rasterizer` at a new device pixel ratio and recompute cell
+    /// metrics, discarding glyphs rasterized at the old scale.
+    fn set_scale_factor(&mut self, dpr: f32) -> Result<(), CreationError> {
+        let family = self.glyph_cache.font.normal.family.clone();
+        let size = self.glyph_cache.font.size.as_f32_pts() as i32;
+
+        let mut ft = Font::new(dpr, &family, size);
+        let font_keys = ft.compute_font_keys()?;
+        let (cell_descent, cell_width, cell_height) =
+            super::glsl3::Glsl3Renderer::compute_cell_size(&mut ft, font_keys.regular)?;
+
+        self.dpr = dpr;
+        self.cell_width = cell_width;
+        self.cell_height = cell_height;
+        self.cell_descent = cell_descent;
+        self.font_keys = font_keys;
+
+        let mut glyph_cache = GlyphCache::new(ft)?;
+        glyph_cache.prefill_ascii(font_keys.regular);
+        self.glyph_cache = glyph_cache;
+
+        Ok(())
+    }
+
+    /// Recompile the shader program from whatever's currently on disk at
+    /// `TEXT_SHADER_*_PATH` and reload the font at the same family/size/dpr,
+    /// for [`super::LiveReloadHandle`] to call after a debounced file change.
+    fn reload(&mut self) -> Result<(), CreationError> {
+        let vertex_shader = create_shader(gl::VERTEX_SHADER, TEXT_SHADER_V_PATH, TEXT_SHADER_V)?;
+        let fragment_shader =
+            create_shader(gl::FRAGMENT_SHADER, TEXT_SHADER_F_PATH, TEXT_SHADER_F)?;
+        let program = create_program(vertex_shader, fragment_shader)?;
+
+        let family = self.glyph_cache.font.normal.family.clone();
+        let size = self.glyph_cache.font.size.as_f32_pts() as i32;
+        let mut ft = Font::new(self.dpr, &family, size);
+        let font_keys = ft.compute_font_keys()?;
+        let (cell_descent, cell_width, cell_height) =
+            super::glsl3::Glsl3Renderer::compute_cell_size(&mut ft, font_keys.regular)?;
+
+        let mut glyph_cache = GlyphCache::new(ft)?;
+        glyph_cache.prefill_ascii(font_keys.regular);
+
+        let (u_cell_size, u_window_size) = unsafe {
+            (
+                gl::GetUniformLocation(program, b"cellSize\0".as_ptr() as *const _),
+                gl::GetUniformLocation(program, b"windowSize\0".as_ptr() as *const _),
+            )
+        };
+
+        unsafe { gl::DeleteProgram(self.program) };
+        self.program = program;
+        self.u_cell_size = u_cell_size;
+        self.u_window_size = u_window_size;
+        self.cell_width = cell_width;
+        self.cell_height = cell_height;
+        self.cell_descent = cell_descent;
+        self.font_keys = font_keys;
+        self.glyph_cache = glyph_cache;
+
+        Ok(())
+    }
+
+    fn shader_paths(&self) -> [&'static str; 2] {
+        [TEXT_SHADER_V_PATH, TEXT_SHADER_F_PATH]
+    }
+
+    fn font_family(&self) -> String {
+        self.glyph_cache.font.normal.family.clone()
+    }
+
+    fn cell_width(&self) -> f32 {
+        self.cell_width
+    }
+
+    fn cell_height(&self) -> f32 {
+        self.cell_height
+    }
+
+    fn cell_descent(&self) -> f32 {
+        self.cell_descent
+    }
+}
+
+/// Per-vertex attributes for the vertex-expansion fallback path. Mirrors
+/// `glsl3::GlInstanceAttr` plus `corner`, since each cell's 4 vertices must be
+/// expanded explicitly rather than replicated by the GPU via instancing.
+#[derive(Debug, Clone, Default)]
+struct GlVertexAttr {
+    // which corner of the cell quad this vertex is (0..3)
+    corner: f32,
+
+    // gridCoords
+    col: f32, // x
+    row: f32, // y
+
+    // bounding box size
+    uv_width: f32,
+    uv_height: f32,
+
+    // bounding origin point
+    uv_offset_x: f32,
+    uv_offset_y: f32,
+
+    // glyph baseline
+    baseline: f32,
+
+    // rect of this glyph within its shared atlas texture, normalized [0, 1]
+    atlas_uv_x: f32,
+    atlas_uv_y: f32,
+    atlas_uv_width: f32,
+    atlas_uv_height: f32,
+
+    // text color the glyph is tinted with
+    fg: [f32; 3],
+}
+
+/// Create the VAO/VBO/EBO shared by every cell's 4 expanded vertices. Both
+/// buffers are left empty (sized by `set_size` once the grid dimensions are
+/// known); only the vertex attribute layout is fixed here.
+fn create_vertex_buffers() -> (GLuint, GLuint, GLuint) {
+    let mut vao: GLuint = 0;
+    let mut vbo: GLuint = 0;
+    let mut ebo: GLuint = 0;
+    unsafe {
+        gl::GenVertexArrays(1, &mut vao);
+        gl::BindVertexArray(vao);
+
+        gl::GenBuffers(1, &mut ebo);
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+        let sizeof_attr = mem::size_of::<GlVertexAttr>();
+        let define_vertex_attrib = |idx, n, offset| {
+            gl::VertexAttribPointer(
+                idx,
+                n,
+                gl::FLOAT,
+                gl::FALSE,
+                sizeof_attr as _,
+                offset as _,
+            );
+            gl::EnableVertexAttribArray(idx);
+            // No VertexAttribDivisor here: unlike glsl3, every vertex is distinct
+            // (already expanded), not replicated from one shared instance.
+            (idx + 1, offset + n * (mem::size_of::<f32>() as i32))
+        };
+
+        let (idx, offset) = (0, 0);
+        // in float cornerAttr
+        let (idx, offset) = define_vertex_attrib(idx, 1, offset);
+        // in vec2 gridCoords
+        let (idx, offset) = define_vertex_attrib(idx, 2, offset);
+        // in vec4 uvAttr
+        let (idx, offset) = define_vertex_attrib(idx, 4, offset);
+        // in float baseline
+        let (idx, offset) = define_vertex_attrib(idx, 1, offset);
+        // in vec4 atlasUvAttr
+        let (idx, offset) = define_vertex_attrib(idx, 4, offset);
+        // in vec3 fgAttr
+        let (idx, offset) = define_vertex_attrib(idx, 3, offset);
+
+        let (_, _) = (idx, offset);
+
+        gl::BindVertexArray(0);
+    }
+
+    (vao, vbo, ebo)
+}